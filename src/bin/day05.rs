@@ -1,79 +1,465 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fmt;
+use std::ops::RangeInclusive;
 
 use anyhow::format_err;
 use anyhow::Result;
+use itertools::Itertools;
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub struct Seat(u8, u8);
 
 impl Seat {
     pub fn from_code(code: &str) -> Result<Seat> {
-        let (row_code, column_code) = code.split_at(7);
-        let row = Self::binary_search(row_code, 'F', 'B')?;
-        let column = Self::binary_search(column_code, 'L', 'R')?;
+        Ok(PlaneLayout::default().decode(code)?)
+    }
 
-        Ok(Seat(row, column))
+    pub fn to_code(&self) -> Result<String> {
+        Ok(PlaneLayout::default().encode(self)?)
     }
 
-    fn binary_search(input: &str, lowc: char, highc: char) -> Result<u8> {
-        let mut low = 0;
-        let mut high = (1 << (input.len() as u8)) - 1;
+    pub fn id(&self) -> u16 {
+        self.0 as u16 * 8 + self.1 as u16
+    }
 
-        for c in input.chars() {
-            let delta = (high - low) / 2 + 1;
-            if c == lowc {
-                high = high - delta;
-            } else if c == highc {
-                low = low + delta;
-            } else {
-                return Err(format_err!(
-                    "invalid input: {}. Expecting {} or {}",
-                    c,
-                    lowc,
-                    highc
-                ));
-            }
+    pub fn from_id(id: u16) -> Seat {
+        Seat((id / 8) as u8, (id % 8) as u8)
+    }
 
-            if low == high {
-                break;
+    /// Every seat on the plane, in ID order.
+    pub fn all() -> impl Iterator<Item = Seat> {
+        (0..ROWS * COLUMNS).map(Seat::from_id)
+    }
+}
+
+/// The seat's code along with its row, column and ID, e.g. `FBFBBFFRLR (row 44, column 5, id 357)`.
+impl fmt::Display for Seat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (row {}, column {}, id {})",
+            self.to_code().unwrap_or_default(),
+            self.0,
+            self.1,
+            self.id()
+        )
+    }
+}
+
+const ROWS: u16 = 128;
+const COLUMNS: u16 = 8;
+
+/// Render the plane row by row, with `#` for an occupied seat and `.` for a free one.
+pub fn render_seat_map(seats: &HashSet<Seat>) -> String {
+    let mut map = String::new();
+    for row in 0..ROWS {
+        map.push_str(&format!("{:3} ", row));
+        for column in 0..COLUMNS {
+            let seat = Seat(row as u8, column as u8);
+            map.push(if seats.contains(&seat) { '#' } else { '.' });
+        }
+        map.push('\n');
+    }
+
+    map
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Occupancy {
+    /// All the free seats, in ID order.
+    pub free_seats: Vec<Seat>,
+    /// Runs of consecutive free seat IDs.
+    pub free_runs: Vec<RangeInclusive<u16>>,
+    /// Completely empty rows at the front of the plane.
+    pub missing_front_rows: Vec<u8>,
+    /// Completely empty rows at the back of the plane.
+    pub missing_back_rows: Vec<u8>,
+    /// Free seats whose neighbours at ID-1 and ID+1 are both taken.
+    pub enclosed_seats: Vec<Seat>,
+}
+
+impl Occupancy {
+    pub fn analyse(seats: &HashSet<Seat>) -> Occupancy {
+        let taken = |id: u16| seats.contains(&Seat::from_id(id));
+
+        let free_seats = Seat::all()
+            .filter(|seat| !seats.contains(seat))
+            .collect::<Vec<_>>();
+
+        let mut free_runs: Vec<RangeInclusive<u16>> = Vec::new();
+        for id in free_seats.iter().map(Seat::id) {
+            match free_runs.last_mut() {
+                Some(run) if *run.end() + 1 == id => *run = *run.start()..=id,
+                _ => free_runs.push(id..=id),
             }
         }
 
-        Ok(low)
+        let row_is_empty = |row: &u8| (0..COLUMNS).all(|c| !seats.contains(&Seat(*row, c as u8)));
+        let missing_front_rows = (0..ROWS as u8).take_while(row_is_empty).collect::<Vec<_>>();
+        let mut missing_back_rows = (0..ROWS as u8)
+            .rev()
+            .take_while(row_is_empty)
+            .filter(|row| !missing_front_rows.contains(row))
+            .collect::<Vec<_>>();
+        missing_back_rows.reverse();
+
+        let enclosed_seats = free_seats
+            .iter()
+            .filter(|seat| {
+                let id = seat.id();
+                id > 0 && taken(id - 1) && taken(id + 1)
+            })
+            .copied()
+            .collect();
+
+        Occupancy {
+            free_seats,
+            free_runs,
+            missing_front_rows,
+            missing_back_rows,
+            enclosed_seats,
+        }
+    }
+}
+
+/// Describes how boarding pass codes map to seats: how many characters encode the row and the
+/// column, and which letters select the lower and upper half at each step. Layouts are built with
+/// [`PlaneLayout::new`] and [`PlaneLayout::with_letters`], which check that codes can be decoded.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PlaneLayout {
+    row_bits: u8,
+    column_bits: u8,
+    front: char,
+    back: char,
+    left: char,
+    right: char,
+}
+
+impl Default for PlaneLayout {
+    fn default() -> Self {
+        PlaneLayout {
+            row_bits: 7,
+            column_bits: 3,
+            front: 'F',
+            back: 'B',
+            left: 'L',
+            right: 'R',
+        }
     }
+}
 
-    pub fn id(&self) -> u16 {
-        self.0 as u16 * 8 + self.1 as u16
+impl PlaneLayout {
+    pub fn new(row_bits: u8, column_bits: u8) -> Result<PlaneLayout> {
+        if row_bits == 0 || row_bits > 8 || column_bits == 0 || column_bits > 8 {
+            return Err(format_err!(
+                "invalid layout: {} row bits and {} column bits (each must be between 1 and 8)",
+                row_bits,
+                column_bits
+            ));
+        }
+
+        Ok(PlaneLayout {
+            row_bits,
+            column_bits,
+            ..PlaneLayout::default()
+        })
+    }
+
+    /// Use other letters for each half. The lower and upper half letters of the row and of the
+    /// column must differ, or codes couldn't be decoded back into the same seat.
+    pub fn with_letters(
+        self,
+        front: char,
+        back: char,
+        left: char,
+        right: char,
+    ) -> Result<PlaneLayout> {
+        if front == back || left == right {
+            return Err(format_err!(
+                "invalid layout: front/back letters {:?}/{:?} and left/right letters {:?}/{:?} \
+                 (each pair must differ)",
+                front,
+                back,
+                left,
+                right
+            ));
+        }
+
+        Ok(PlaneLayout {
+            front,
+            back,
+            left,
+            right,
+            ..self
+        })
+    }
+
+    pub fn row_bits(&self) -> u8 {
+        self.row_bits
+    }
+
+    pub fn column_bits(&self) -> u8 {
+        self.column_bits
+    }
+
+    /// The front, back, left and right letters, in the same order as for
+    /// [`PlaneLayout::with_letters`].
+    pub fn letters(&self) -> (char, char, char, char) {
+        (self.front, self.back, self.left, self.right)
+    }
+
+    pub fn rows(&self) -> u16 {
+        1 << self.row_bits
+    }
+
+    pub fn columns(&self) -> u16 {
+        1 << self.column_bits
+    }
+
+    pub fn code_len(&self) -> usize {
+        (self.row_bits + self.column_bits) as usize
+    }
+
+    /// Decode a boarding pass code, reading each half as a binary number where the "lower half"
+    /// letter is a 0 and the "upper half" letter is a 1.
+    pub fn decode(&self, code: &str) -> Result<Seat, CodeError> {
+        let chars = code.chars().collect::<Vec<_>>();
+        if chars.len() != self.code_len() {
+            return Err(CodeError::WrongLength {
+                expected: self.code_len(),
+                found: chars.len(),
+            });
+        }
+
+        let (row_code, column_code) = chars.split_at(self.row_bits as usize);
+        let row = Self::decode_bits(row_code, 0, self.front, self.back)?;
+        let column = Self::decode_bits(column_code, row_code.len(), self.left, self.right)?;
+
+        Ok(Seat(row, column))
+    }
+
+    fn decode_bits(input: &[char], offset: usize, low: char, high: char) -> Result<u8, CodeError> {
+        input.iter().enumerate().try_fold(0u8, |acc, (i, &c)| {
+            let bit = if c == low {
+                0
+            } else if c == high {
+                1
+            } else {
+                return Err(CodeError::InvalidChar {
+                    position: offset + i,
+                    found: c,
+                    expected: (low, high),
+                });
+            };
+            // `acc << 1` can't overflow since a layout has at most 8 bits per half
+            Ok((acc << 1) | bit)
+        })
+    }
+
+    pub fn encode(&self, seat: &Seat) -> Result<String, CodeError> {
+        let Seat(row, column) = *seat;
+        if row as u16 >= self.rows() || column as u16 >= self.columns() {
+            return Err(CodeError::SeatOutOfRange {
+                row,
+                column,
+                rows: self.rows(),
+                columns: self.columns(),
+            });
+        }
+
+        let mut code = String::with_capacity(self.code_len());
+        Self::encode_bits(&mut code, row, self.row_bits, self.front, self.back);
+        Self::encode_bits(&mut code, column, self.column_bits, self.left, self.right);
+
+        Ok(code)
+    }
+
+    fn encode_bits(code: &mut String, value: u8, bits: u8, low: char, high: char) {
+        for i in (0..bits).rev() {
+            code.push(if value & (1 << i) == 0 { low } else { high });
+        }
+    }
+
+    /// Seat ID for this layout, i.e. `row * columns + column`.
+    pub fn seat_id(&self, seat: &Seat) -> u16 {
+        seat.0 as u16 * self.columns() + seat.1 as u16
     }
 }
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CodeError {
+    WrongLength {
+        expected: usize,
+        found: usize,
+    },
+    InvalidChar {
+        position: usize,
+        found: char,
+        expected: (char, char),
+    },
+    SeatOutOfRange {
+        row: u8,
+        column: u8,
+        rows: u16,
+        columns: u16,
+    },
+}
+
+impl fmt::Display for CodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeError::WrongLength { expected, found } => write!(
+                f,
+                "invalid code length: expected {} characters, found {}",
+                expected, found
+            ),
+            CodeError::InvalidChar {
+                position,
+                found,
+                expected: (low, high),
+            } => write!(
+                f,
+                "invalid input: {:?} at position {}. Expecting {} or {}",
+                found, position, low, high
+            ),
+            CodeError::SeatOutOfRange {
+                row,
+                column,
+                rows,
+                columns,
+            } => write!(
+                f,
+                "seat ({}, {}) is outside of a plane with {} rows and {} columns",
+                row, column, rows, columns
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CodeError {}
+
+/// A list of boarding passes, keeping track of the line(s) each seat was found on.
+#[derive(Debug, Default)]
+pub struct Manifest {
+    lines: BTreeMap<Seat, Vec<usize>>,
+}
+
+impl Manifest {
+    pub fn parse(input: &str) -> Result<Manifest> {
+        let mut manifest = Manifest::default();
+        for (i, line) in input.lines().enumerate() {
+            let seat = Seat::from_code(line).map_err(|e| format_err!("line {}: {}", i + 1, e))?;
+            manifest.lines.entry(seat).or_default().push(i + 1);
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn seats(&self) -> HashSet<Seat> {
+        self.lines.keys().copied().collect()
+    }
+
+    /// Seats that appear on more than one boarding pass, along with the (1-based) line numbers
+    /// of each pass.
+    pub fn duplicates(&self) -> Vec<(Seat, &[usize])> {
+        self.lines
+            .iter()
+            .filter(|(_, lines)| lines.len() > 1)
+            .map(|(seat, lines)| (*seat, lines.as_slice()))
+            .collect()
+    }
+
+    pub fn diff<'a>(&self, other: &'a Manifest) -> ManifestDiff<'a> {
+        ManifestDiff {
+            added: other
+                .lines
+                .keys()
+                .filter(|seat| !self.lines.contains_key(seat))
+                .copied()
+                .collect(),
+            removed: self
+                .lines
+                .keys()
+                .filter(|seat| !other.lines.contains_key(seat))
+                .copied()
+                .collect(),
+            double_booked: other.duplicates(),
+        }
+    }
+}
+
+/// Differences between an old and a new manifest.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ManifestDiff<'a> {
+    /// Seats only in the new manifest.
+    pub added: Vec<Seat>,
+    /// Seats only in the old manifest.
+    pub removed: Vec<Seat>,
+    /// Seats booked more than once in the new manifest.
+    pub double_booked: Vec<(Seat, &'a [usize])>,
+}
+
+impl fmt::Display for ManifestDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for seat in &self.added {
+            writeln!(f, "+ {}", seat)?;
+        }
+        for seat in &self.removed {
+            writeln!(f, "- {}", seat)?;
+        }
+        for (seat, lines) in &self.double_booked {
+            writeln!(f, "! {} on lines {}", seat, lines.iter().join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn diff_manifests(old: &str, new: &str) -> Result<()> {
+    let old = Manifest::parse(&std::fs::read_to_string(old)?)?;
+    let new = Manifest::parse(&std::fs::read_to_string(new)?)?;
+
+    print!("{}", old.diff(&new));
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
+    let args = env::args().collect::<Vec<_>>();
+    if let (Some(old), Some(new)) = (
+        advent20::arg_after(&args, "--diff", 1)?,
+        advent20::arg_after(&args, "--diff", 2)?,
+    ) {
+        return diff_manifests(old, new);
+    }
+
     let input = advent20::input_string()?;
 
-    let seats = input
-        .lines()
-        .map(|line| Seat::from_code(line))
-        .collect::<Result<HashSet<Seat>>>()?;
+    let manifest = Manifest::parse(&input)?;
+    // duplicates point at a data error, but each seat still counts once for the answers
+    for (seat, lines) in manifest.duplicates() {
+        eprintln!(
+            "duplicate boarding pass: {} on lines {}",
+            seat,
+            lines.iter().join(", ")
+        );
+    }
+    let seats = manifest.seats();
 
     let max_id = seats.iter().map(Seat::id).max().unwrap();
-    let min_id = seats.iter().map(Seat::id).min().unwrap();
 
     println!("part 1: {}", max_id);
 
-    let mut all_seats = HashSet::with_capacity(128);
-    for row in 0..128 {
-        for column in 0..8 {
-            let seat = Seat(row, column);
-            if seat.id() >= min_id && seat.id() <= max_id {
-                all_seats.insert(seat);
-            }
-        }
+    if args.iter().any(|arg| arg == "--map") {
+        print!("{}", render_seat_map(&seats));
     }
 
-    let seat_id = all_seats
-        .difference(&seats)
+    let occupancy = Occupancy::analyse(&seats);
+    let seat_id = occupancy
+        .enclosed_seats
+        .first()
         .map(Seat::id)
-        .next()
         .ok_or(format_err!("No remaining seat!"))?;
 
     println!("part 2: {}", seat_id);
@@ -97,4 +483,141 @@ mod tests {
 
         assert_eq!(Seat(44, 5), seat);
     }
+
+    #[test]
+    fn test_seat_to_code() {
+        assert_eq!("FBFBBFFRLR", Seat(44, 5).to_code().unwrap());
+        assert_eq!("BBFFBBFRLL", Seat(102, 4).to_code().unwrap());
+        assert_eq!(
+            "FBFBBFFRLR (row 44, column 5, id 357)",
+            Seat(44, 5).to_string()
+        );
+    }
+
+    #[test]
+    fn test_malformed_codes() {
+        let layout = PlaneLayout::default();
+        assert_eq!(
+            Err(CodeError::WrongLength {
+                expected: 10,
+                found: 3
+            }),
+            layout.decode("FBF")
+        );
+        assert_eq!(
+            Err(CodeError::InvalidChar {
+                position: 8,
+                found: 'X',
+                expected: ('L', 'R')
+            }),
+            layout.decode("FBFBBFFRXR")
+        );
+        assert_eq!(
+            Err(CodeError::InvalidChar {
+                position: 0,
+                found: 'L',
+                expected: ('F', 'B')
+            }),
+            layout.decode("LBFBBFFRLR")
+        );
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        let layout = PlaneLayout::new(4, 2)
+            .unwrap()
+            .with_letters('U', 'D', '<', '>')
+            .unwrap();
+        assert_eq!((4, 2), (layout.row_bits(), layout.column_bits()));
+        assert_eq!(('U', 'D', '<', '>'), layout.letters());
+        assert_eq!(6, layout.code_len());
+
+        let seat = layout.decode("DUUD><").unwrap();
+        assert_eq!(Seat(9, 2), seat);
+        assert_eq!(38, layout.seat_id(&seat));
+        assert_eq!("DUUD><", layout.encode(&seat).unwrap());
+
+        assert!(layout.encode(&Seat(16, 0)).is_err());
+        assert!(PlaneLayout::new(9, 3).is_err());
+        assert!(PlaneLayout::default()
+            .with_letters('F', 'F', 'L', 'R')
+            .is_err());
+        assert!(PlaneLayout::default()
+            .with_letters('F', 'B', 'R', 'R')
+            .is_err());
+    }
+
+    #[test]
+    fn test_occupancy() {
+        // rows 0 and 127 are empty, row 1 is full, and row 2 has seats 2 and 5 free
+        let mut seats = HashSet::new();
+        for row in 1..127 {
+            for column in 0..8 {
+                seats.insert(Seat(row, column));
+            }
+        }
+        seats.remove(&Seat(2, 2));
+        seats.remove(&Seat(2, 5));
+        seats.remove(&Seat(2, 6));
+
+        let occupancy = Occupancy::analyse(&seats);
+        assert_eq!(vec![0], occupancy.missing_front_rows);
+        assert_eq!(vec![127], occupancy.missing_back_rows);
+        assert_eq!(8 + 3 + 8, occupancy.free_seats.len());
+        assert_eq!(
+            vec![0..=7, 18..=18, 21..=22, 1016..=1023],
+            occupancy.free_runs
+        );
+        assert_eq!(vec![Seat(2, 2)], occupancy.enclosed_seats);
+    }
+
+    #[test]
+    fn test_render_seat_map() {
+        let seats = vec![Seat(0, 0), Seat(0, 7), Seat(1, 3)]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let map = render_seat_map(&seats);
+        let mut lines = map.lines();
+
+        assert_eq!(Some("  0 #......#"), lines.next());
+        assert_eq!(Some("  1 ...#...."), lines.next());
+        assert_eq!(128, map.lines().count());
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let manifest = Manifest::parse("FBFBBFFRLR\nBFFFBBFRRR\nFBFBBFFRLR\nFFFBBBFRRR").unwrap();
+
+        assert_eq!(3, manifest.seats().len());
+        assert_eq!(vec![(Seat(44, 5), &[1, 3][..])], manifest.duplicates());
+    }
+
+    #[test]
+    fn test_manifest_diff() {
+        let old = Manifest::parse("FBFBBFFRLR\nBFFFBBFRRR\nFFFBBBFRRR").unwrap();
+        let new = Manifest::parse("FBFBBFFRLR\nBBFFBBFRLL\nBBFFBBFRLL").unwrap();
+        let diff = old.diff(&new);
+
+        assert_eq!(vec![Seat(102, 4)], diff.added);
+        assert_eq!(vec![Seat(14, 7), Seat(70, 7)], diff.removed);
+        assert_eq!(vec![(Seat(102, 4), &[2, 3][..])], diff.double_booked);
+        assert_eq!(
+            "+ BBFFBBFRLL (row 102, column 4, id 820)\n\
+             - FFFBBBFRRR (row 14, column 7, id 119)\n\
+             - BFFFBBFRRR (row 70, column 7, id 567)\n\
+             ! BBFFBBFRLL (row 102, column 4, id 820) on lines 2, 3\n",
+            diff.to_string()
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let layout = PlaneLayout::default();
+        for row in 0..128 {
+            for column in 0..8 {
+                let seat = Seat(row, column);
+                assert_eq!(seat, layout.decode(&layout.encode(&seat).unwrap()).unwrap());
+            }
+        }
+    }
 }
@@ -1,67 +1,562 @@
-use anyhow::Result;
+use std::cmp::Ordering;
+use std::env;
+use std::fmt;
+
+use anyhow::{format_err, Result};
 use bitvec::prelude::*;
+use itertools::Itertools;
+use serde::Serialize;
 
-fn char_index(c: char) -> usize {
-    let ascii_code = c as u8;
-    let a = 'a' as u8;
+/// A line holding just this is a member who answered nothing, since a blank line would end the
+/// group instead. It still counts as an answer when it comes with others.
+pub const NO_ANSWERS: &str = "-";
 
-    (ascii_code - a) as usize
+/// The set of symbols seen in the answers, each one mapped to a bit index.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Alphabet {
+    symbols: Vec<char>,
 }
 
-fn main() -> Result<()> {
-    let input = advent20::input_string()?;
+impl Alphabet {
+    pub fn from_input(input: &str) -> Alphabet {
+        let mut symbols = input
+            .lines()
+            .filter(|line| line.trim() != NO_ANSWERS)
+            .flat_map(str::chars)
+            .filter(|c| !c.is_whitespace())
+            .collect::<Vec<_>>();
+        symbols.sort_unstable();
+        symbols.dedup();
+
+        Alphabet { symbols }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn index(&self, c: char) -> Option<usize> {
+        self.symbols.binary_search(&c).ok()
+    }
+
+    pub fn symbol(&self, index: usize) -> Option<char> {
+        self.symbols.get(index).copied()
+    }
+
+    /// The symbols whose bits are set in `answers`, in alphabet order.
+    pub fn symbols(&self, answers: &BitSlice) -> String {
+        answers
+            .iter()
+            .zip(&self.symbols)
+            .filter_map(|(bit, c)| if *bit { Some(*c) } else { None })
+            .collect()
+    }
+}
 
-    let mut groups = Vec::new();
-    let mut current_group = None;
+/// The answers of each member of a group, as one bitset per member.
+#[derive(Debug, Clone)]
+pub struct Group {
+    width: usize,
+    members: Vec<BitVec>,
+}
 
-    for line in input.lines() {
-        if line.is_empty() {
-            groups.push(current_group.take().unwrap());
-            continue;
+impl Group {
+    fn new(width: usize) -> Group {
+        Group {
+            width,
+            members: Vec::new(),
         }
+    }
+
+    pub fn members(&self) -> &[BitVec] {
+        &self.members
+    }
+
+    /// Questions to which anyone in the group answered "yes".
+    pub fn anyone(&self) -> BitVec {
+        self.members
+            .iter()
+            .fold(bitvec![0; self.width], |mut acc, bv| {
+                acc |= bv.clone();
+                acc
+            })
+    }
 
-        let group = current_group.get_or_insert(Vec::new());
-        let mut answers = bitarr![0; 26];
-        for c in line.chars() {
-            answers.set(char_index(c), true);
+    /// Questions to which everyone in the group answered "yes".
+    pub fn everyone(&self) -> BitVec {
+        self.members
+            .iter()
+            .fold(bitvec![1; self.width], |mut acc, bv| {
+                acc &= bv.clone();
+                acc
+            })
+    }
+
+    /// Number of members that answered "yes" to each question.
+    pub fn histogram(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.width];
+        for member in &self.members {
+            for (count, bit) in counts.iter_mut().zip(member.iter()) {
+                if *bit {
+                    *count += 1;
+                }
+            }
         }
 
-        group.push(answers);
+        counts
     }
 
-    if let Some(group) = current_group.take() {
-        groups.push(group);
+    /// Questions to which at least `k` members answered "yes".
+    pub fn at_least(&self, k: usize) -> BitVec {
+        self.histogram()
+            .into_iter()
+            .map(|count| count >= k)
+            .collect()
     }
 
-    let sum: usize = groups
-        .iter()
-        .map(|answers| {
-            answers
+    /// Questions to which exactly `k` members answered "yes".
+    pub fn exactly(&self, k: usize) -> BitVec {
+        self.histogram()
+            .into_iter()
+            .map(|count| count == k)
+            .collect()
+    }
+}
+
+/// A customs declaration survey: the groups of the input, each made of members with their
+/// answers.
+#[derive(Debug, Clone)]
+pub struct Survey {
+    pub alphabet: Alphabet,
+    pub groups: Vec<Group>,
+}
+
+impl Survey {
+    /// Parse the answers of each member, one per line, with groups separated by blank lines and
+    /// [`NO_ANSWERS`] for members who answered nothing. Leading and trailing whitespace is ignored,
+    /// but whitespace inside a line is an error.
+    pub fn parse(input: &str) -> Result<Survey> {
+        let alphabet = Alphabet::from_input(input);
+
+        let mut groups = Vec::new();
+        let mut current_group = None;
+
+        for (line_no, line) in input.lines().map(str::trim).enumerate() {
+            if line.is_empty() {
+                groups.extend(current_group.take());
+                continue;
+            }
+
+            let group = current_group.get_or_insert_with(|| Group::new(alphabet.len()));
+            let mut answers = bitvec![0; alphabet.len()];
+            if line == NO_ANSWERS {
+                group.members.push(answers);
+                continue;
+            }
+            for c in line.chars() {
+                // the alphabet has every symbol of the input except whitespace
+                let index = alphabet.index(c).ok_or_else(|| {
+                    format_err!("line {}: whitespace in answers {:?}", line_no + 1, line)
+                })?;
+                answers.set(index, true);
+            }
+
+            group.members.push(answers);
+        }
+
+        groups.extend(current_group.take());
+
+        Ok(Survey { alphabet, groups })
+    }
+
+    /// Jaccard similarity between the questions answered by anyone in group `a` and in group `b`.
+    pub fn similarity(&self, a: usize, b: usize) -> f64 {
+        jaccard(&self.groups[a].anyone(), &self.groups[b].anyone())
+    }
+
+    /// Compute the analytics report. `top` is the number of most similar pairs of groups to
+    /// include.
+    pub fn report(&self, top: usize) -> Report {
+        let mut member_counts = vec![0; self.alphabet.len()];
+        let mut group_counts = vec![0; self.alphabet.len()];
+        for group in &self.groups {
+            for (count, n) in member_counts.iter_mut().zip(group.histogram()) {
+                *count += n;
+            }
+            for (count, bit) in group_counts.iter_mut().zip(group.anyone().iter()) {
+                if *bit {
+                    *count += 1;
+                }
+            }
+        }
+
+        let questions = member_counts
+            .iter()
+            .zip(&group_counts)
+            .enumerate()
+            .map(|(i, (&members, &groups))| QuestionStats {
+                question: self.alphabet.symbol(i).unwrap(),
+                members,
+                groups,
+            })
+            .collect::<Vec<_>>();
+
+        let max = member_counts.iter().max().copied().unwrap_or(0);
+        let min = member_counts.iter().min().copied().unwrap_or(0);
+        let with_count = |n: usize| {
+            questions
                 .iter()
-                .fold(bitvec![0; 26], |mut acc, bv| {
-                    acc |= bv.to_bitvec();
-                    acc
-                })
-                .count_ones()
-        })
-        .sum();
+                .filter(|q| q.members == n)
+                .map(|q| q.question)
+                .collect::<Vec<_>>()
+        };
+
+        let unanimous_groups = self
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| !g.members.is_empty() && g.anyone() == g.everyone())
+            .map(|(i, _)| i)
+            .collect();
+
+        let silent_members = self
+            .groups
+            .iter()
+            .enumerate()
+            .flat_map(|(group, g)| {
+                g.members
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, answers)| answers.not_any())
+                    .map(move |(member, _)| MemberRef { group, member })
+            })
+            .collect();
+
+        let anyone = self.groups.iter().map(Group::anyone).collect::<Vec<_>>();
+        let mut most_similar = (0..anyone.len())
+            .tuple_combinations()
+            .map(|(a, b)| Similarity {
+                a,
+                b,
+                jaccard: jaccard(&anyone[a], &anyone[b]),
+            })
+            .collect::<Vec<_>>();
+        most_similar.sort_by(|x, y| {
+            y.jaccard
+                .partial_cmp(&x.jaccard)
+                .unwrap_or(Ordering::Equal)
+                .then((x.a, x.b).cmp(&(y.a, y.b)))
+        });
+        most_similar.truncate(top);
+
+        Report {
+            groups: self.groups.len(),
+            members: self.groups.iter().map(|g| g.members.len()).sum(),
+            most_common: with_count(max),
+            least_common: with_count(min),
+            questions,
+            unanimous_groups,
+            silent_members,
+            most_similar,
+        }
+    }
+}
+
+fn jaccard(a: &BitSlice, b: &BitSlice) -> f64 {
+    let (intersection, union) = a.iter().zip(b.iter()).fold((0, 0), |(i, u), (x, y)| {
+        (i + (*x && *y) as usize, u + (*x || *y) as usize)
+    });
+    if union == 0 {
+        // two empty sets are identical
+        1.0
+    } else {
+        intersection as f64 / union as f64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuestionStats {
+    pub question: char,
+    /// Number of members that answered "yes".
+    pub members: usize,
+    /// Number of groups where anyone answered "yes".
+    pub groups: usize,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub struct MemberRef {
+    pub group: usize,
+    pub member: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Similarity {
+    pub a: usize,
+    pub b: usize,
+    pub jaccard: f64,
+}
+
+/// Survey analytics. Groups and members are referred to by their 0-based index in the input.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub groups: usize,
+    pub members: usize,
+    pub questions: Vec<QuestionStats>,
+    pub most_common: Vec<char>,
+    pub least_common: Vec<char>,
+    pub unanimous_groups: Vec<usize>,
+    pub silent_members: Vec<MemberRef>,
+    pub most_similar: Vec<Similarity>,
+}
+
+impl Report {
+    /// One table per question, unanimous group, silent member and similar pair of groups, in
+    /// that order, each with a header and separated by a blank line.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("question,members,groups\n");
+        for q in &self.questions {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                csv_field(q.question),
+                q.members,
+                q.groups
+            ));
+        }
+        csv.push_str("\nunanimous_group\n");
+        for group in &self.unanimous_groups {
+            csv.push_str(&format!("{}\n", group));
+        }
+        csv.push_str("\ngroup,member\n");
+        for m in &self.silent_members {
+            csv.push_str(&format!("{},{}\n", m.group, m.member));
+        }
+        csv.push_str("\na,b,jaccard\n");
+        for s in &self.most_similar {
+            csv.push_str(&format!("{},{},{}\n", s.a, s.b, s.jaccard));
+        }
+
+        csv
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+fn csv_field(c: char) -> String {
+    match c {
+        ',' => "\",\"".to_string(),
+        '"' => "\"\"\"\"".to_string(),
+        _ => c.to_string(),
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} groups, {} members", self.groups, self.members)?;
+        writeln!(f)?;
+        writeln!(f, "question  members  groups")?;
+        for q in &self.questions {
+            writeln!(f, "{:>8}  {:>7}  {:>6}", q.question, q.members, q.groups)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "most common:      {}", self.most_common.iter().join(" "))?;
+        writeln!(
+            f,
+            "least common:     {}",
+            self.least_common.iter().join(" ")
+        )?;
+        writeln!(
+            f,
+            "unanimous groups: {}",
+            self.unanimous_groups.iter().join(" ")
+        )?;
+        writeln!(
+            f,
+            "silent members:   {}",
+            self.silent_members
+                .iter()
+                .map(|m| format!("{}/{}", m.group, m.member))
+                .join(" ")
+        )?;
+        writeln!(f)?;
+        writeln!(f, "most similar groups:")?;
+        for s in &self.most_similar {
+            writeln!(f, "{:>5} {:>5}  {:.3}", s.a, s.b, s.jaccard)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let input = advent20::input_string()?;
+
+    let survey = Survey::parse(&input)?;
+
+    let args = env::args().collect::<Vec<_>>();
+    if let Some(pos) = args.iter().position(|arg| arg == "--report") {
+        let report = survey.report(10);
+        match args.get(pos + 1).map(String::as_str) {
+            None | Some("table") => print!("{}", report),
+            Some("csv") => print!("{}", report.to_csv()),
+            Some("json") => println!("{}", report.to_json()?),
+            Some(format) => return Err(format_err!("unknown report format: {}", format)),
+        }
+        return Ok(());
+    }
+
+    let sum: usize = survey.groups.iter().map(|g| g.anyone().count_ones()).sum();
 
     println!("part 1: {}", sum);
 
-    let sum: usize = groups
+    let sum: usize = survey
+        .groups
         .iter()
-        .map(|answers| {
-            answers
-                .iter()
-                .fold(bitvec![1; 26], |mut acc, bv| {
-                    acc &= bv.to_bitvec();
-                    acc
-                })
-                .count_ones()
-        })
+        .map(|g| g.everyone().count_ones())
         .sum();
 
     println!("part 2: {}", sum);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r"abc
+
+a
+b
+c
+
+ab
+ac
+
+a
+a
+a
+a
+
+b";
+
+    #[test]
+    fn test_anyone_everyone() {
+        let groups = Survey::parse(INPUT).unwrap().groups;
+
+        assert_eq!(5, groups.len());
+        assert_eq!(
+            11,
+            groups
+                .iter()
+                .map(|g| g.anyone().count_ones())
+                .sum::<usize>()
+        );
+        assert_eq!(
+            6,
+            groups
+                .iter()
+                .map(|g| g.everyone().count_ones())
+                .sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_arbitrary_alphabet() {
+        let Survey { alphabet, groups } = Survey::parse("xÉ7\n7-\n\nZ").unwrap();
+
+        assert_eq!("-7ZxÉ", alphabet.symbols(&bitvec![1; 5]));
+        assert_eq!("-7xÉ", alphabet.symbols(&groups[0].anyone()));
+        assert_eq!("7", alphabet.symbols(&groups[0].everyone()));
+        assert_eq!("Z", alphabet.symbols(&groups[1].everyone()));
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let survey = Survey::parse(" ab \n\tb\n\n  \nc\n").unwrap();
+        assert_eq!(2, survey.groups.len());
+        assert_eq!("abc", survey.alphabet.symbols(&bitvec![1; 3]));
+
+        let error = Survey::parse("a\n\nab c\nb").unwrap_err();
+        assert!(error.to_string().starts_with("line 3:"));
+    }
+
+    #[test]
+    fn test_quorum() {
+        let Survey { alphabet, groups } = Survey::parse("abc\nab\na\nd").unwrap();
+        let group = &groups[0];
+
+        assert_eq!(vec![3, 2, 1, 1], group.histogram());
+        assert_eq!("ab", alphabet.symbols(&group.at_least(2)));
+        assert_eq!("cd", alphabet.symbols(&group.exactly(1)));
+        assert_eq!("a", alphabet.symbols(&group.exactly(3)));
+        assert_eq!("", alphabet.symbols(&group.at_least(5)));
+    }
+
+    #[test]
+    fn test_report() {
+        let survey = Survey::parse(INPUT).unwrap();
+        let report = survey.report(2);
+
+        assert_eq!(5, report.groups);
+        assert_eq!(11, report.members);
+        assert_eq!(
+            QuestionStats {
+                question: 'a',
+                members: 8,
+                groups: 4
+            },
+            report.questions[0]
+        );
+        assert_eq!(vec!['a'], report.most_common);
+        assert_eq!(vec!['c'], report.least_common);
+        assert_eq!(vec![0, 3, 4], report.unanimous_groups);
+        assert!(report.silent_members.is_empty());
+        assert_eq!(
+            vec![
+                Similarity {
+                    a: 0,
+                    b: 1,
+                    jaccard: 1.0
+                },
+                Similarity {
+                    a: 0,
+                    b: 2,
+                    jaccard: 1.0
+                }
+            ],
+            report.most_similar
+        );
+        assert!((survey.similarity(2, 3) - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            "question,members,groups\na,8,4\nb,4,4\nc,3,3\n\
+             \nunanimous_group\n0\n3\n4\n\
+             \ngroup,member\n\
+             \na,b,jaccard\n0,1,1\n0,2,1\n",
+            report.to_csv()
+        );
+    }
+
+    #[test]
+    fn test_silent_members() {
+        let survey = Survey::parse("ab\nb\n-\n\n-a").unwrap();
+        assert_eq!("-ab", survey.alphabet.symbols(&bitvec![1; 3]));
+        assert_eq!(3, survey.groups[0].members().len());
+
+        let report = survey.report(0);
+        assert_eq!(
+            vec![MemberRef {
+                group: 0,
+                member: 2
+            }],
+            report.silent_members
+        );
+        assert!(report.to_csv().contains("\ngroup,member\n0,2\n"));
+        assert!(report.to_json().unwrap().contains("\"silent_members\""));
+    }
+}
@@ -1,158 +1,113 @@
-use std::{collections::HashSet, convert::TryFrom};
-
-use anyhow::format_err;
-use anyhow::Result;
-use nom::{
-    branch::alt,
-    bytes::complete::tag,
-    character::complete::alpha1,
-    character::complete::digit1,
-    combinator::value,
-    combinator::{map, map_res},
-    multi::separated_list1,
-    sequence::tuple,
-    Finish, IResult,
-};
-use petgraph::{graphmap::DiGraphMap, algo::all_simple_paths};
-
-fn colour(input: &str) -> IResult<&str, Colour> {
-    map(tuple((alpha1, tag(" "), alpha1)), |(adj, _, colour)| {
-        Colour(format!("{} {}", adj, colour))
-    })(input)
-}
-
-fn contain_rule(input: &str) -> IResult<&str, ContainRule> {
-    map(
-        tuple((
-            map_res(digit1, |s: &str| s.parse::<u8>()),
-            tag(" "),
-            colour,
-            alt((tag(" bags"), tag(" bag"))),
-        )),
-        |(num, _, colour, _)| ContainRule(num, colour),
-    )(input)
-}
-
-fn rule(input: &str) -> IResult<&str, Rule> {
-    map(
-        tuple((
-            colour,
-            tag(" bags contain "),
-            alt((
-                value(Vec::new(), tag("no other bags")),
-                separated_list1(tag(", "), contain_rule),
-            )),
-            tag("."),
-        )),
-        |(colour, _, contain_rules, _)| Rule {
-            colour,
-            contains: contain_rules,
-        },
-    )(input)
-}
-
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Colour(String);
-
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct ContainRule(u8, Colour);
-
-#[derive(Debug)]
-pub struct Rule {
-    pub colour: Colour,
-    pub contains: Vec<ContainRule>,
-}
-
-impl TryFrom<&str> for Rule {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        rule(value)
-            .finish()
-            .map(|(_, rule)| rule)
-            .map_err(|e| format_err!("Failed to parse rule: {}", e.to_string()))
-    }
-}
-
-fn part1(rules: &[Rule]) -> usize {
-    let mut graph = DiGraphMap::new();
-    for rule in rules {
-        if rule.contains.is_empty() {
-            graph.add_edge(rule.colour.0.as_str(), "no other", 0);
-        } else {
-            rule.contains.iter().for_each(|c| {
-                graph.add_edge(rule.colour.0.as_str(), c.1.0.as_str(), c.0);
-            });
-        }
-    }
+use std::env;
 
-    let mut graph = graph.into_graph::<u32>();
-    graph.reverse();
-    // dbg!(&graph);
-    let shiny_gold = graph.node_indices().find(|i| graph[*i] == "shiny gold").unwrap();
-    let sink = graph.node_indices().find(|i| graph[*i] == "no other").unwrap();
+use anyhow::{ensure, Result};
 
-    dbg!(shiny_gold);
-    dbg!(sink);
+use advent20::day07::{parse_rules, validate_rules, BagGraph, Rule};
 
-    let paths = all_simple_paths::<Vec<_>, _>(&graph, sink, shiny_gold, 1, None);
+const TARGET: &str = "shiny gold";
 
-    // dbg!(paths.count());
-
-    let mut set = HashSet::new();
-    for path in paths {
-        for node in dbg!(path) {
-            set.insert(graph[node]);
-        }
-    }
-
-    // "no other" and "shiny gold" are both included in the set, so account for that in the total
-    return set.len() - 2;
+fn part1(rules: &[Rule], target: &str) -> usize {
+    BagGraph::new(rules).containers_of(target).len()
 }
 
-fn parse_rules(input: &str) -> Result<Vec<Rule>> {
-    input
-        .lines()
-        .map(Rule::try_from)
-        .collect::<Result<Vec<Rule>>>()
+/// Total number of bags that a bag of the given colour must contain.
+fn part2(rules: &[Rule], target: &str) -> Result<u64> {
+    BagGraph::new(rules).count_inside(target)
 }
 
 fn main() -> Result<()> {
     let input = advent20::input_string()?;
 
+    let args = env::args().collect::<Vec<_>>();
+
     let rules = parse_rules(&input)?;
-    let count = part1(&rules);
+    for error in validate_rules(&rules) {
+        eprintln!("warning: {}", error);
+    }
+
+    let target = match args.get(1).map(String::as_str) {
+        Some("--dot") => {
+            print!(
+                "{}",
+                BagGraph::new(&rules).to_dot(args.get(2).map(String::as_str))
+            );
+            return Ok(());
+        }
+        Some("--json") => {
+            let json = BagGraph::new(&rules).to_json(args.get(2).map(String::as_str))?;
+            println!("{}", json);
+            return Ok(());
+        }
+        Some(colour) => colour,
+        None => TARGET,
+    };
+    ensure!(
+        BagGraph::new(&rules).has_colour(target),
+        "unknown colour: {}",
+        target
+    );
+
+    let count = part1(&rules, target);
     println!("part 1: {}", count);
 
+    let count = part2(&rules, target)?;
+    println!("part 2: {}", count);
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use advent20::day07::EXAMPLE;
 
     #[test]
-    fn test_contain_rule() {
+    fn test_part1() {
+        let rules = parse_rules(EXAMPLE).unwrap();
+
+        assert_eq!(4, part1(&rules, TARGET));
+        assert_eq!(32, part2(&rules, TARGET).unwrap());
+
+        let graph = BagGraph::new(&rules);
+        let mut contents = graph.contents_of(TARGET).into_iter().collect::<Vec<_>>();
+        contents.sort_unstable();
+        assert_eq!(
+            vec!["dark olive", "dotted black", "faded blue", "vibrant plum"],
+            contents
+        );
+        let mut containers = graph
+            .containers_of("dark olive")
+            .into_iter()
+            .collect::<Vec<_>>();
+        containers.sort_unstable();
         assert_eq!(
-            contain_rule("3 muted magenta bag"),
-            Ok(("", ContainRule(3, Colour("muted magenta".to_string()))))
+            vec![
+                "bright white",
+                "dark orange",
+                "light red",
+                "muted yellow",
+                "shiny gold"
+            ],
+            containers
         );
+        assert!(graph.containers_of("light red").is_empty());
+        assert!(graph.containers_of("unknown colour").is_empty());
     }
 
     #[test]
-    fn test_part1() {
-        let input = r#"light red bags contain 1 bright white bag, 2 muted yellow bags.
-dark orange bags contain 3 bright white bags, 4 muted yellow bags.
-bright white bags contain 1 shiny gold bag.
-muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
-shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
-dark olive bags contain 3 faded blue bags, 4 dotted black bags.
-vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
-faded blue bags contain no other bags.
-dotted black bags contain no other bags."#;
+    fn test_part2() {
+        let input = r#"shiny gold bags contain 2 dark red bags.
+dark red bags contain 2 dark orange bags.
+dark orange bags contain 2 dark yellow bags.
+dark yellow bags contain 2 dark green bags.
+dark green bags contain 2 dark blue bags.
+dark blue bags contain 2 dark violet bags.
+dark violet bags contain no other bags."#;
 
         let rules = parse_rules(input).unwrap();
 
-        assert_eq!(4, part1(&rules));
+        assert_eq!(126, part2(&rules, TARGET).unwrap());
+        assert_eq!(2, part2(&rules, "dark blue").unwrap());
+        assert_eq!(0, part2(&rules, "dark violet").unwrap());
     }
 }
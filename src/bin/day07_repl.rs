@@ -0,0 +1,202 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+
+use anyhow::{format_err, Result};
+use itertools::Itertools;
+
+use advent20::day07::{parse_rules, validate_rules, BagGraph};
+
+const HELP: &str = "\
+commands:
+  contains <colour>           colours that can eventually contain a <colour> bag
+  inside <colour>             colours that a <colour> bag can eventually contain
+  count <colour>              total number of bags inside a <colour> bag
+  depth <colour>              how deeply bags are nested inside a <colour> bag
+  path <colour> -> <colour>   shortest chain of bags from one colour to another
+  help                        show this message
+  quit                        exit";
+
+/// Colours are matched with any amount of whitespace between words, like in the rules.
+fn normalize(colour: &str) -> String {
+    colour.split_whitespace().join(" ")
+}
+
+fn known_colour(graph: &BagGraph, colour: &str) -> Result<String> {
+    let colour = normalize(colour);
+    if colour.is_empty() {
+        Err(format_err!("missing colour"))
+    } else if !graph.has_colour(&colour) {
+        Err(format_err!("unknown colour: {}", colour))
+    } else {
+        Ok(colour)
+    }
+}
+
+fn sorted_list(colours: impl IntoIterator<Item = impl AsRef<str>>) -> String {
+    let mut colours = colours
+        .into_iter()
+        .map(|c| c.as_ref().to_string())
+        .collect::<Vec<_>>();
+    colours.sort_unstable();
+    let count = colours.len();
+    colours.push(format!(
+        "({} colour{})",
+        count,
+        if count == 1 { "" } else { "s" }
+    ));
+
+    colours.join("\n")
+}
+
+fn run_command(graph: &BagGraph, line: &str) -> Result<String> {
+    let line = line.trim();
+    let (command, args) = match line.find(char::is_whitespace) {
+        Some(pos) => (&line[..pos], &line[pos..]),
+        None => (line, ""),
+    };
+
+    match command {
+        "help" => Ok(HELP.to_string()),
+        "contains" => {
+            let colour = known_colour(graph, args)?;
+            Ok(sorted_list(graph.containers_of(&colour)))
+        }
+        "inside" => {
+            let colour = known_colour(graph, args)?;
+            Ok(sorted_list(graph.contents_of(&colour)))
+        }
+        "count" => {
+            let colour = known_colour(graph, args)?;
+            Ok(graph.count_inside(&colour)?.to_string())
+        }
+        "depth" => {
+            let colour = known_colour(graph, args)?;
+            Ok(graph.depth(&colour)?.to_string())
+        }
+        "path" => {
+            let (from, to) = args
+                .split("->")
+                .collect_tuple()
+                .ok_or_else(|| format_err!("usage: path <colour> -> <colour>"))?;
+            let (from, to) = (known_colour(graph, from)?, known_colour(graph, to)?);
+            let path = graph
+                .shortest_path(&from, &to)
+                .ok_or_else(|| format_err!("{} bags can't contain {} bags", from, to))?;
+            let total = path
+                .iter()
+                .try_fold(1u64, |total, (_, num)| total.checked_mul(*num as u64))
+                .ok_or_else(|| format_err!("too many {} bags to count", to))?;
+
+            Ok(format!(
+                "{}\n({} {} bag{})",
+                path.iter()
+                    .skip(1)
+                    .fold(from.clone(), |acc, (colour, num)| format!(
+                        "{} -> {} x {}",
+                        acc, num, colour
+                    )),
+                total,
+                to,
+                if total == 1 { "" } else { "s" }
+            ))
+        }
+        "" => Ok(String::new()),
+        _ => Err(format_err!("unknown command: {} (try 'help')", command)),
+    }
+}
+
+fn main() -> Result<()> {
+    let args = env::args().collect::<Vec<_>>();
+    let path = args
+        .get(1)
+        .ok_or_else(|| format_err!("usage: {} <rules file>", args[0]))?;
+    let input = std::fs::read_to_string(path)?;
+
+    let rules = parse_rules(&input)?;
+    for error in validate_rules(&rules) {
+        eprintln!("warning: {}", error);
+    }
+    let graph = BagGraph::new(&rules);
+    println!(
+        "loaded {} rules, type 'help' for a list of commands",
+        rules.len()
+    );
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        if matches!(line.trim(), "quit" | "exit") {
+            break;
+        }
+
+        match run_command(&graph, &line) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output),
+            Err(e) => println!("error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use advent20::day07::EXAMPLE;
+
+    #[test]
+    fn test_commands() {
+        let rules = parse_rules(EXAMPLE).unwrap();
+        let graph = BagGraph::new(&rules);
+        let run = |line: &str| run_command(&graph, line).map_err(|e| e.to_string());
+
+        assert_eq!(
+            Ok("bright white\ndark orange\nlight red\nmuted yellow\n(4 colours)".to_string()),
+            run("contains shiny   gold")
+        );
+        assert_eq!(
+            Ok("dark olive\ndotted black\nfaded blue\nvibrant plum\n(4 colours)".to_string()),
+            run("inside shiny gold")
+        );
+        assert_eq!(Ok("32".to_string()), run("count shiny gold"));
+        assert_eq!(Ok("4".to_string()), run("depth light red"));
+        assert_eq!(
+            Ok("light red -> 1 x bright white -> 1 x shiny gold -> 1 x dark olive\n(1 dark olive bag)"
+                .to_string()),
+            run("path light red -> dark olive")
+        );
+        assert_eq!(
+            Err("shiny gold bags can't contain light red bags".to_string()),
+            run("path shiny gold -> light red")
+        );
+        assert_eq!(Err("unknown colour: plaid".to_string()), run("count plaid"));
+        assert!(run("frobnicate").is_err());
+        assert_eq!(Ok(String::new()), run("   "));
+
+        // 255^10 bags don't fit in a u64
+        let letters = "abcdefghijk".chars().collect::<Vec<_>>();
+        let mut input = letters
+            .windows(2)
+            .map(|w| format!("{0}{0} x bags contain 255 {1}{1} x bags.\n", w[0], w[1]))
+            .collect::<String>();
+        input.push_str("kk x bags contain no other bags.");
+        let rules = parse_rules(&input).unwrap();
+        let graph = BagGraph::new(&rules);
+        assert!(run_command(&graph, "path cc x -> kk x")
+            .unwrap()
+            .ends_with("(17878103347812890625 kk x bags)"));
+        assert_eq!(
+            "too many kk x bags to count",
+            run_command(&graph, "path aa x -> kk x")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+}
@@ -1,96 +1,26 @@
-use std::collections::HashSet;
+use std::env;
+use std::time::Instant;
 
 use anyhow::*;
-use nom::{
-    branch::alt,
-    bytes::complete::tag,
-    character::complete::char,
-    character::complete::digit1,
-    combinator::{map, map_res},
-    sequence::tuple,
-    Finish, IResult,
-};
-
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
-pub enum Op {
-    Acc,
-    Jmp,
-    Nop,
-}
-
-impl std::fmt::Display for Op {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", match *self {
-            Self::Acc => "acc",
-            Self::Jmp => "jmp",
-            Self::Nop => "nop",
-        })
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
-pub struct Inst {
-    op: Op,
-    offset: i32,
-}
-
-impl std::fmt::Display for Inst {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {:+}", self.op, self.offset)
-    }
-}
 
-fn parse_inst(input: &str) -> IResult<&str, Inst> {
-    map(
-        tuple((
-            alt((tag("acc"), tag("jmp"), tag("nop"))),
-            tag(" "),
-            alt((char('-'), char('+'))),
-            map_res(digit1, |s: &str| s.parse::<i32>()),
-        )),
-        |(op, _, sign, mut offset)| {
-            if sign == '-' {
-                offset *= -1;
-            }
-            let op = match op {
-                "acc" => Op::Acc,
-                "jmp" => Op::Jmp,
-                "nop" => Op::Nop,
-                _ => unreachable!(),
-            };
-            Inst { op, offset }
-        },
-    )(input)
-}
+use advent20::day08::asm::{self, Dialect};
+use advent20::day08::cfg::Cfg;
+use advent20::day08::fast::Compiled;
+use advent20::day08::search::{self, SearchOptions};
+use advent20::day08::trace::{self, StepDiff, Trace};
+use advent20::day08::{parse_program, repair, Console, ExitReason, Inst, Op, Repair};
 
-fn check_prg_termination(prg: &[Inst]) -> Option<i32> {
-    let mut acc = 0i32;
-    let mut addr = 0i32;
-    let mut visited_addr = HashSet::new();
-    loop {
-        if visited_addr.contains(&addr) {
-            // infinite loop
-            break None;
-        } else if addr == prg.len() as i32 {
-            break Some(acc);
-        } else {
-            visited_addr.insert(addr);
-        }
-        let i = &prg[addr as usize];
-        match i.op {
-            Op::Acc => {
-                acc += i.offset;
-                addr += 1;
-            }
-            Op::Jmp => addr += i.offset,
-            Op::Nop => addr += 1,
-        }
+fn check_prg_termination(prg: Vec<Inst>) -> Option<i32> {
+    let mut console = Console::new(prg);
+    match console.run() {
+        ExitReason::Terminated => Some(console.state().acc),
+        _ => None,
     }
 }
 
 fn mutate_prg<F>(prg: &[Inst], addr: i32, mutation: F) -> Vec<Inst>
 where
-    F: Fn(&mut Inst)
+    F: Fn(&mut Inst),
 {
     let mut new_prg = prg.to_vec();
     if let Some(i) = new_prg.get_mut(addr as usize) {
@@ -100,19 +30,15 @@ where
     new_prg
 }
 
-fn parse_program(input: &str) -> Result<Vec<Inst>> {
-    input
-        .lines()
-        .map(|line| {
-            parse_inst(line)
-                .finish()
-                .map(|v| v.1)
-                .map_err(|e| format_err!("Failed to parse instruction: {}", e.to_string()))
-        })
-        .collect::<Result<Vec<_>>>()
+fn part1(prg: &[Inst]) -> Result<i32> {
+    match Console::new(prg.to_vec()).run() {
+        ExitReason::InfiniteLoop { acc, .. } => Ok(acc),
+        reason => Err(format_err!("Program didn't loop: {:?}", reason)),
+    }
 }
 
-fn part2(prg: &[Inst]) -> Option<i32> {
+/// Try flipping each `nop` and `jmp` in turn, running the whole program each time.
+fn part2_brute_force(prg: &[Inst]) -> Option<i32> {
     let nop_addrs: Vec<_> = prg
         .iter()
         .enumerate()
@@ -126,71 +52,226 @@ fn part2(prg: &[Inst]) -> Option<i32> {
 
     nop_addrs
         .iter()
-        .find_map(|addr| check_prg_termination(&mutate_prg(&prg, *addr as i32, |mut i| i.op = Op::Jmp)))
+        .find_map(|addr| check_prg_termination(mutate_prg(prg, *addr as i32, |i| i.op = Op::Jmp)))
         .or_else(|| {
-            jmp_addrs
-                .iter()
-                .find_map(|addr| check_prg_termination(&mutate_prg(&prg, *addr as i32, |mut i| i.op = Op::Nop)))
+            jmp_addrs.iter().find_map(|addr| {
+                check_prg_termination(mutate_prg(prg, *addr as i32, |i| i.op = Op::Nop))
+            })
         })
 }
 
+fn part2(prg: &[Inst]) -> Result<Repair> {
+    repair(prg).ok_or_else(|| {
+        format_err!("The program already terminates, or no single flip makes it terminate")
+    })
+}
+
+/// Time both repair strategies over the given number of runs, then both interpreters over a
+/// hundred times as many.
+fn bench(prg: &[Inst], runs: u32) {
+    let start = Instant::now();
+    for _ in 0..runs {
+        part2_brute_force(prg);
+    }
+    let brute_force = start.elapsed() / runs;
+
+    let start = Instant::now();
+    for _ in 0..runs {
+        repair(prg);
+    }
+    let linear = start.elapsed() / runs;
+
+    println!("brute force: {:?} per run", brute_force);
+    println!("linear:      {:?} per run", linear);
+
+    let runs = runs * 100;
+    let start = Instant::now();
+    for _ in 0..runs {
+        Console::new(prg.to_vec()).run();
+    }
+    let reference = start.elapsed() / runs;
+
+    let start = Instant::now();
+    for _ in 0..runs {
+        Compiled::new(prg).run();
+    }
+    let compiled = start.elapsed() / runs;
+
+    println!("reference interpreter: {:?} per run", reference);
+    println!("compiled:              {:?} per run", compiled);
+}
+
+/// Print the steps where two traces differ.
+fn diff_traces(old: &str, new: &str) -> Result<()> {
+    let old = Trace::from_json_lines(&std::fs::read_to_string(old)?)?;
+    let new = Trace::from_json_lines(&std::fs::read_to_string(new)?)?;
+
+    let diffs = trace::diff(old.entries(), new.entries());
+    let same = diffs
+        .iter()
+        .take_while(|d| matches!(d, StepDiff::Same(_)))
+        .count();
+    println!("{} identical steps before the traces diverge", same);
+    diffs
+        .iter()
+        .filter(|d| !matches!(d, StepDiff::Same(_)))
+        .for_each(|d| println!("{}", d));
+
+    Ok(())
+}
+
+/// Print what static analysis of the control flow graph finds out about the program.
+fn cfg_report(prg: &[Inst]) {
+    let cfg = Cfg::new(prg);
+    let blocks = cfg.basic_blocks();
+    println!("{} basic blocks", blocks.len());
+    for block in &blocks {
+        println!(
+            "  {:>4}..{:<4} {}",
+            block.start, block.end, prg[block.start]
+        );
+    }
+
+    let unreachable = cfg.unreachable();
+    println!(
+        "{} unreachable instructions: {:?}",
+        unreachable.len(),
+        unreachable
+    );
+
+    for addrs in cfg.infinite_loops() {
+        println!("infinite loop through {:?}", addrs);
+    }
+    if cfg.always_loops() {
+        println!("the program never terminates");
+    }
+
+    for (addr, target) in cfg.leaving_jumps() {
+        println!(
+            "{}: {} jumps outside the program to {}",
+            addr, prg[addr], target
+        );
+    }
+}
+
 fn main() -> Result<()> {
+    let args = env::args().collect::<Vec<_>>();
+
+    if let (Some(old), Some(new)) = (
+        advent20::arg_after(&args, "--diff-traces", 1)?,
+        advent20::arg_after(&args, "--diff-traces", 2)?,
+    ) {
+        return diff_traces(old, new);
+    }
+
+    if let Some(path) = advent20::arg_after(&args, "--assemble", 1)? {
+        let dialect = if args.iter().any(|arg| arg == "--extended") {
+            Dialect::Extended
+        } else {
+            Dialect::Standard
+        };
+        let prg = asm::assemble(&std::fs::read_to_string(path)?, dialect)?;
+        print!("{}", asm::disassemble(&prg));
+        return Ok(());
+    }
+
     let input = advent20::input_string()?;
 
     let prg = parse_program(&input)?;
-    // dbg!(instructions);
-
-    let mut acc = 0i32;
-    let mut addr = 0i32;
-    let mut visited_addr = HashSet::new();
-    loop {
-        if visited_addr.contains(&addr) {
-            break;
-        } else {
-            visited_addr.insert(addr);
+
+    if args.iter().any(|arg| arg == "--cfg") {
+        cfg_report(&prg);
+        return Ok(());
+    }
+
+    if args.iter().any(|arg| arg == "--cfg-dot") {
+        print!("{}", Cfg::new(&prg).to_dot());
+        return Ok(());
+    }
+
+    if let Some(max_patches) = advent20::arg_after(&args, "--repair-all", 1)? {
+        let options = SearchOptions {
+            max_patches: max_patches.parse()?,
+            offsets: args.iter().any(|arg| arg == "--offsets"),
+        };
+        let repairs = search::repair_all(&prg, options);
+        if repairs.is_empty() {
+            println!("no repair with at most {} patches", options.max_patches);
         }
-        let i = &prg[addr as usize];
-        match i.op {
-            Op::Acc => {
-                acc += i.offset;
-                addr += 1;
-            }
-            Op::Jmp => addr += i.offset,
-            Op::Nop => addr += 1,
+        for repair in repairs {
+            let patches = repair
+                .patches
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>();
+            println!("{}: {}", repair.acc, patches.join(", "));
         }
+        return Ok(());
+    }
+
+    if args.iter().any(|arg| arg == "--bench") {
+        bench(&prg, 100);
+        return Ok(());
+    }
+
+    if let Some(path) = advent20::arg_after(&args, "--trace", 1)? {
+        let max_visits = advent20::flag_value(&args, "--max-visits", 1)?;
+        let (reason, trace) = trace::record_visits(prg.clone(), max_visits);
+        std::fs::write(path, trace.to_json_lines()?)?;
+        eprintln!("recorded {} steps: {:?}", trace.entries().len(), reason);
     }
 
+    if let Some(path) = advent20::arg_after(&args, "--trace-repaired", 1)? {
+        let res = part2(&prg)?;
+        let (reason, trace) =
+            trace::record(mutate_prg(&prg, res.addr as i32, |i| *i = res.patched));
+        std::fs::write(path, trace.to_json_lines()?)?;
+        eprintln!("recorded {} steps: {:?}", trace.entries().len(), reason);
+    }
+
+    if let Some(path) = advent20::arg_after(&args, "--replay", 1)? {
+        let trace = Trace::from_json_lines(&std::fs::read_to_string(path)?)?;
+        let reason = trace::replay(prg.clone(), &trace)?;
+        eprintln!("replayed {} steps: {:?}", trace.entries().len(), reason);
+    }
+
+    let acc = part1(&prg)?;
+
     println!("part 1: {}", acc);
 
-    let res = part2(&prg).unwrap();
+    let res = part2(&prg)?;
 
-    println!("part 2: {}", res);
+    println!(
+        "part 2: {} (flipped {} to {} at address {})",
+        res.acc, res.original, res.patched, res.addr
+    );
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use advent20::day08::EXAMPLE;
 
     #[test]
     fn test() {
-        let input = r"nop +0
-acc +1
-jmp +4
-acc +3
-jmp -3
-acc -99
-acc +1
-jmp -4
-acc +6";
-        let prg = parse_program(input).unwrap();
+        let prg = parse_program(EXAMPLE).unwrap();
         println!("prg:");
         prg.iter().for_each(|i| println!("{}", i));
 
         println!("prg2:");
-        let prg2 = mutate_prg(&prg, 2, |mut i| i.op = Op::Nop);
+        let prg2 = mutate_prg(&prg, 2, |i| i.op = Op::Nop);
         prg2.iter().for_each(|i| println!("{}", i));
 
-        assert_eq!(Some(8), part2(&prg));
+        assert_eq!(5, part1(&prg).unwrap());
+        assert_eq!(Some(8), part2_brute_force(&prg));
+        assert_eq!(8, part2(&prg).unwrap().acc);
+    }
+
+    #[test]
+    fn test_repair_matches_brute_force() {
+        let prg = parse_program(&std::fs::read_to_string("input/day08.txt").unwrap()).unwrap();
+
+        assert_eq!(part2_brute_force(&prg), part2(&prg).ok().map(|r| r.acc));
     }
 }
@@ -0,0 +1,431 @@
+use std::env;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use anyhow::{format_err, Result};
+
+use advent20::day08::asm::{self, Dialect};
+use advent20::day08::{flip, parse_program, Console, Inst, Op, State};
+
+const HELP: &str = "\
+commands:
+  step [n]                    execute the next n instructions (default 1)
+  continue                    run until a breakpoint is hit or the program stops
+  state                       show the accumulator and program counter
+  list [addr] [n]             disassemble n instructions around addr (default: around pc)
+  break <addr>                stop before executing the instruction at addr
+  break <op>                  stop before executing any instruction of that kind: acc, jmp
+                              or nop, and mul, jz or hlt with --extended
+  break acc <op> <value>      stop when the accumulator starts matching a condition,
+                              where <op> is one of < <= == != >= >
+  breakpoints                 list breakpoints
+  delete <n>                  remove breakpoint number n
+  patch <addr> flip           swap a jmp for a nop or the other way round
+  patch <addr> <op> <offset>  replace an instruction, e.g. 'patch 7 jmp -3'
+  patch <addr> <offset>       change the offset of an instruction, e.g. 'patch 7 +2'
+  reset                       go back to the start of the program, keeping patches
+  help                        show this message
+  quit                        exit";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Cmp {
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+}
+
+impl Cmp {
+    fn parse(s: &str) -> Result<Cmp> {
+        match s {
+            "<" => Ok(Cmp::Lt),
+            "<=" => Ok(Cmp::Le),
+            "==" => Ok(Cmp::Eq),
+            "!=" => Ok(Cmp::Ne),
+            ">=" => Ok(Cmp::Ge),
+            ">" => Ok(Cmp::Gt),
+            _ => Err(format_err!("unknown comparison: {}", s)),
+        }
+    }
+
+    fn eval(self, a: i32, b: i32) -> bool {
+        match self {
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Eq => a == b,
+            Cmp::Ne => a != b,
+            Cmp::Ge => a >= b,
+            Cmp::Gt => a > b,
+        }
+    }
+}
+
+impl fmt::Display for Cmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Eq => "==",
+            Cmp::Ne => "!=",
+            Cmp::Ge => ">=",
+            Cmp::Gt => ">",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Breakpoint {
+    Addr(i32),
+    Op(Op),
+    Acc(Cmp, i32),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Addr(addr) => write!(f, "address {}", addr),
+            Breakpoint::Op(op) => write!(f, "any {}", op),
+            Breakpoint::Acc(cmp, value) => write!(f, "acc {} {}", cmp, value),
+        }
+    }
+}
+
+struct Debugger {
+    console: Console,
+    breakpoints: Vec<Breakpoint>,
+    /// Which operations `patch` and `break` accept.
+    dialect: Dialect,
+}
+
+impl Debugger {
+    fn new(prg: Vec<Inst>, dialect: Dialect) -> Debugger {
+        Debugger {
+            console: Console::new(prg),
+            breakpoints: Vec::new(),
+            dialect,
+        }
+    }
+
+    /// An operation of the debugger's dialect.
+    fn parse_op(&self, s: &str) -> Result<Op> {
+        let op = s.parse::<Op>()?;
+        if op.is_extended() && self.dialect != Dialect::Extended {
+            return Err(format_err!(
+                "{} is only available in the extended dialect (use --extended)",
+                op
+            ));
+        }
+
+        Ok(op)
+    }
+
+    /// Disassembly of a single instruction, marking the current one and breakpoints.
+    fn disassemble(&self, addr: usize) -> String {
+        let pc = self.console.state().pc;
+        let inst = &self.console.program()[addr];
+        let is_break = self.breakpoints.iter().any(|bp| match bp {
+            Breakpoint::Addr(a) => *a == addr as i32,
+            Breakpoint::Op(op) => *op == inst.op,
+            Breakpoint::Acc(..) => false,
+        });
+
+        format!(
+            "{}{} {:>5}  {}",
+            if pc == addr as i32 { '>' } else { ' ' },
+            if is_break { '*' } else { ' ' },
+            addr,
+            inst
+        )
+    }
+
+    fn describe_state(&self) -> String {
+        let State { acc, pc } = self.console.state();
+        let current = if pc >= 0 && (pc as usize) < self.console.program().len() {
+            self.disassemble(pc as usize)
+        } else {
+            format!(">  {:>5}  <end of program>", pc)
+        };
+
+        format!("acc = {}, pc = {}\n{}", acc, pc, current)
+    }
+
+    /// Index of the breakpoint that fires for this step, if any. Address and operation
+    /// breakpoints look at the next instruction to execute, accumulator breakpoints fire when
+    /// the condition goes from false to true.
+    fn hit_breakpoint(&self, before: State) -> Option<usize> {
+        let State { acc, pc } = self.console.state();
+        let next = self.console.program().get(pc as usize).filter(|_| pc >= 0);
+        self.breakpoints.iter().position(|bp| match bp {
+            Breakpoint::Addr(addr) => *addr == pc,
+            Breakpoint::Op(op) => next.is_some_and(|i| i.op == *op),
+            Breakpoint::Acc(cmp, value) => cmp.eval(acc, *value) && !cmp.eval(before.acc, *value),
+        })
+    }
+
+    /// Execute up to `max_steps` instructions (or as many as possible if `None`), stopping at
+    /// breakpoints.
+    fn run(&mut self, max_steps: Option<usize>) -> String {
+        let mut steps = 0;
+        loop {
+            if max_steps.is_some_and(|max| steps >= max) {
+                return self.describe_state();
+            }
+
+            let before = self.console.state();
+            if let Some(reason) = self.console.step() {
+                return format!("program stopped: {:?}\n{}", reason, self.describe_state());
+            }
+            steps += 1;
+
+            if let Some(n) = self.hit_breakpoint(before) {
+                return format!(
+                    "breakpoint {} ({}) hit after {} steps\n{}",
+                    n,
+                    self.breakpoints[n],
+                    steps,
+                    self.describe_state()
+                );
+            }
+        }
+    }
+
+    fn parse_addr(&self, s: &str) -> Result<usize> {
+        let addr = s
+            .parse::<usize>()
+            .map_err(|_| format_err!("invalid address: {}", s))?;
+        if addr >= self.console.program().len() {
+            return Err(format_err!("no instruction at {}", addr));
+        }
+
+        Ok(addr)
+    }
+
+    fn command(&mut self, line: &str) -> Result<String> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [] => Ok(String::new()),
+            ["help"] => Ok(HELP.to_string()),
+            ["step"] => Ok(self.run(Some(1))),
+            ["step", n] => Ok(self.run(Some(n.parse()?))),
+            ["continue"] => Ok(self.run(None)),
+            ["state"] => Ok(self.describe_state()),
+            ["list", rest @ ..] => {
+                let len = self.console.program().len();
+                let (center, count) = match rest {
+                    [] => (self.console.state().pc.max(0) as usize, 10),
+                    [addr] => (self.parse_addr(addr)?, 10),
+                    [addr, n] => (self.parse_addr(addr)?, n.parse()?),
+                    _ => return Err(format_err!("usage: list [addr] [n]")),
+                };
+                let start = center.saturating_sub(count / 2).min(len);
+                let end = (start + count).min(len);
+
+                Ok((start..end)
+                    .map(|addr| self.disassemble(addr))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            ["break", "acc", cmp, value] => {
+                self.breakpoints
+                    .push(Breakpoint::Acc(Cmp::parse(cmp)?, value.parse()?));
+                Ok(format!("breakpoint {} set", self.breakpoints.len() - 1))
+            }
+            ["break", what] => {
+                let bp = match what.parse::<Op>() {
+                    Ok(_) => Breakpoint::Op(self.parse_op(what)?),
+                    Err(_) => Breakpoint::Addr(self.parse_addr(what)? as i32),
+                };
+                self.breakpoints.push(bp);
+                Ok(format!("breakpoint {} set", self.breakpoints.len() - 1))
+            }
+            ["breakpoints"] => Ok(self
+                .breakpoints
+                .iter()
+                .enumerate()
+                .map(|(n, bp)| format!("{}: {}", n, bp))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ["delete", n] => {
+                let n = n.parse::<usize>()?;
+                if n >= self.breakpoints.len() {
+                    return Err(format_err!("no breakpoint {}", n));
+                }
+                let bp = self.breakpoints.remove(n);
+                Ok(format!("deleted breakpoint {} ({})", n, bp))
+            }
+            ["patch", addr, rest @ ..] => {
+                let addr = self.parse_addr(addr)?;
+                let current = self.console.program()[addr];
+                let inst = match rest {
+                    ["flip"] => flip(&current),
+                    [offset] => Inst {
+                        offset: offset.parse()?,
+                        ..current
+                    },
+                    [op, offset] => Inst {
+                        op: self.parse_op(op)?,
+                        offset: offset.parse()?,
+                    },
+                    _ => {
+                        return Err(format_err!(
+                            "usage: patch <addr> flip|<offset>|<op> <offset>"
+                        ))
+                    }
+                };
+                let old = self.console.patch(addr, inst)?;
+                Ok(format!("{}: {} -> {}", addr, old, inst))
+            }
+            ["reset"] => {
+                self.console.reset();
+                Ok(self.describe_state())
+            }
+            [command, ..] => Err(format_err!("unknown command: {} (try 'help')", command)),
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let args = env::args().collect::<Vec<_>>();
+    let path = args
+        .iter()
+        .skip(1)
+        .find(|arg| !arg.starts_with("--"))
+        .ok_or_else(|| format_err!("usage: {} <program file> [--extended]", args[0]))?;
+    let source = std::fs::read_to_string(path)?;
+    let (prg, dialect) = if args.iter().any(|arg| arg == "--extended") {
+        (
+            asm::assemble(&source, Dialect::Extended)?,
+            Dialect::Extended,
+        )
+    } else {
+        (parse_program(&source)?, Dialect::Standard)
+    };
+
+    let mut debugger = Debugger::new(prg, dialect);
+    println!(
+        "loaded {} instructions, type 'help' for a list of commands",
+        debugger.console.program().len()
+    );
+    println!("{}", debugger.describe_state());
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        if matches!(line.trim(), "quit" | "exit") {
+            break;
+        }
+
+        match debugger.command(&line) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output),
+            Err(e) => println!("error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use advent20::day08::EXAMPLE;
+
+    fn debugger() -> Debugger {
+        let prg = parse_program(EXAMPLE).unwrap();
+        Debugger::new(prg, Dialect::Standard)
+    }
+
+    #[test]
+    fn test_step_and_state() {
+        let mut dbg = debugger();
+
+        assert_eq!(
+            "acc = 0, pc = 1\n>      1  acc +1",
+            dbg.command("step").unwrap()
+        );
+        assert_eq!(
+            "acc = 1, pc = 6\n>      6  acc +1",
+            dbg.command("step 2").unwrap()
+        );
+        assert_eq!(
+            "program stopped: InfiniteLoop { acc: 5, pc: 1 }\nacc = 5, pc = 1\n>      1  acc +1",
+            dbg.command("continue").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut dbg = debugger();
+
+        dbg.command("break 3").unwrap();
+        dbg.command("break acc >= 2").unwrap();
+        assert_eq!(
+            "0: address 3\n1: acc >= 2",
+            dbg.command("breakpoints").unwrap()
+        );
+
+        let output = dbg.command("continue").unwrap();
+        assert!(output.starts_with("breakpoint 1 (acc >= 2) hit after 4 steps"));
+        assert_eq!(7, dbg.console.state().pc);
+
+        let output = dbg.command("continue").unwrap();
+        assert!(output.starts_with("breakpoint 0 (address 3) hit after 1 steps"));
+
+        dbg.command("delete 0").unwrap();
+        dbg.command("break jmp").unwrap();
+        let output = dbg.command("continue").unwrap();
+        assert!(output.starts_with("breakpoint 1 (any jmp) hit after 1 steps"));
+        assert!(dbg.command("delete 5").is_err());
+    }
+
+    #[test]
+    fn test_patch() {
+        let mut dbg = debugger();
+
+        assert_eq!("7: jmp -4 -> nop -4", dbg.command("patch 7 flip").unwrap());
+        assert_eq!("8: acc +6 -> acc +7", dbg.command("patch 8 +7").unwrap());
+        assert_eq!(
+            "0: nop +0 -> jmp +1",
+            dbg.command("patch 0 jmp +1").unwrap()
+        );
+        assert!(dbg.command("patch 9 flip").is_err());
+        assert!(dbg.command("patch 1 mul +2").is_err());
+
+        let output = dbg.command("continue").unwrap();
+        assert!(output.starts_with("program stopped: Terminated"));
+        assert_eq!(9, dbg.console.state().acc);
+
+        dbg.command("reset").unwrap();
+        dbg.command("break acc").unwrap();
+        let output = dbg.command("continue").unwrap();
+        assert!(output.starts_with("breakpoint 0 (any acc) hit after 1 steps"));
+        assert_eq!(
+            "       0  jmp +1\n>*     1  acc +1\n       2  jmp +4",
+            dbg.command("list 1 3").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_extended_dialect() {
+        let mut dbg = debugger();
+        assert!(dbg.command("break jz").is_err());
+        assert!(dbg.command("patch 1 hlt +0").is_err());
+
+        dbg.dialect = Dialect::Extended;
+        assert_eq!("breakpoint 0 set", dbg.command("break jz").unwrap());
+        assert_eq!(
+            "1: acc +1 -> mul +2",
+            dbg.command("patch 1 mul +2").unwrap()
+        );
+    }
+}
@@ -1,53 +1,50 @@
+use std::env;
+use std::io;
+
 use anyhow::*;
-use itertools::Itertools;
-
-fn check(previous: &[u64], n: u64) -> Option<u64> {
-    let found = previous
-        .iter()
-        .cartesian_product(previous.iter())
-        .find(|(&a, &b)| (a != b) && (a + b == n));
-
-    if found.is_none() {
-        Some(n)
-    } else {
-        None
-    }
+
+use advent20::day09::{
+    find_contiguous_sum, first_invalid, parse_numbers, validate_stream, XmasValidator, PREAMBLE,
+};
+
+/// Check numbers from stdin as they arrive, printing each invalid one straight away.
+fn stream(preamble: usize) -> Result<()> {
+    let mut validator = XmasValidator::new(preamble);
+    let mut invalid = 0;
+    let count = validate_stream(io::stdin().lock(), &mut validator, |position, n| {
+        invalid += 1;
+        println!("{}: {}", position, n);
+    })?;
+    eprintln!("{} numbers, {} invalid", count, invalid);
+
+    Ok(())
 }
 
 fn main() -> Result<()> {
+    let args = env::args().collect::<Vec<_>>();
+    let preamble = advent20::flag_value(&args, "--preamble", PREAMBLE)?;
+
+    if args.iter().any(|arg| arg == "--stream") {
+        return stream(preamble);
+    }
+
     let input = advent20::input_string()?;
 
-    let nums = input
-        .lines()
-        .map(|s| s.parse::<u64>().map_err(|e| e.into()))
-        .collect::<Result<Vec<_>>>()?;
+    let nums = parse_numbers(&input)?;
 
-    let weakness = nums
-        .windows(26)
-        .find_map(|window| check(&window[0..25], *window.last().unwrap()))
-        .ok_or(format_err!("No solution found!"))?;
+    let (_, weakness) =
+        first_invalid(&nums, preamble).ok_or_else(|| format_err!("No solution found!"))?;
 
     println!("part 1: {}", weakness);
 
-    let mut encryption_weakness = 0;
-    for window_size in 2.. {
-        let found = nums.windows(window_size)
-            .find_map(|w| {
-                if w.iter().sum::<u64>() == weakness {
-                    let min = w.iter().min().unwrap();
-                    let max = w.iter().max().unwrap();
-                    Some(min + max)
-                } else {
-                    None
-                }
-            });
-        if let Some(res) = found {
-            encryption_weakness = res;
-            break;
-        }
-    }
+    let run = find_contiguous_sum(&nums, weakness)?;
 
-    println!("part 2: {}", encryption_weakness);
+    println!(
+        "part 2: {} (positions {} to {})",
+        run.weakness,
+        run.range.start,
+        run.range.end - 1
+    );
 
     Ok(())
 }
@@ -0,0 +1,62 @@
+use std::env;
+
+use anyhow::{format_err, Result};
+
+use advent20::day09::generate::{generate, Spec};
+use advent20::day09::PREAMBLE;
+
+const USAGE: &str = "\
+usage: day09_generate <output file> [--length n] [--preamble n] [--invalid n] [--seed n]
+
+Writes the numbers to <output file>, the expected answers to <output file>.expected in the
+same format as day09 prints them, and the injected invalid numbers to <output file>.invalid
+in the same format as day09 --stream prints them.";
+
+fn main() -> Result<()> {
+    let args = env::args().collect::<Vec<_>>();
+    let path = args
+        .get(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .ok_or_else(|| format_err!("{}", USAGE))?;
+
+    let spec = Spec {
+        length: advent20::flag_value(&args, "--length", 1000)?,
+        preamble: advent20::flag_value(&args, "--preamble", PREAMBLE)?,
+        invalid: advent20::flag_value(&args, "--invalid", 1)?,
+        seed: advent20::flag_value(&args, "--seed", 2020)?,
+    };
+    let generated = generate(spec)?;
+
+    let nums = generated
+        .nums
+        .iter()
+        .map(|n| format!("{}\n", n))
+        .collect::<String>();
+    std::fs::write(path, nums)?;
+
+    let range = &generated.weakness_range;
+    let expected = format!(
+        "part 1: {}\npart 2: {} (positions {} to {})\n",
+        generated.part1(),
+        generated.part2(),
+        range.start,
+        range.end - 1
+    );
+    std::fs::write(format!("{}.expected", path), expected)?;
+
+    let invalid = generated
+        .invalid
+        .iter()
+        .map(|(position, n)| format!("{}: {}\n", position, n))
+        .collect::<String>();
+    std::fs::write(format!("{}.invalid", path), invalid)?;
+
+    eprintln!(
+        "wrote {} numbers with {} invalid ones to {}",
+        generated.nums.len(),
+        generated.invalid.len(),
+        path
+    );
+
+    Ok(())
+}
@@ -1,8 +1,30 @@
-use cached::proc_macro::cached;
-use cached::UnboundCache;
+use std::collections::BTreeMap;
+use std::env;
 
 use anyhow::*;
 
+/// How adapters can be chained: each one takes an input between `min_step` and `max_step` jolts
+/// lower than its rating. The chain starts at the outlet, and the device is rated
+/// `device_offset` jolts above the highest adapter.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct AdapterSpec {
+    min_step: u64,
+    max_step: u64,
+    outlet: u64,
+    device_offset: u64,
+}
+
+impl Default for AdapterSpec {
+    fn default() -> Self {
+        AdapterSpec {
+            min_step: 1,
+            max_step: 3,
+            outlet: 0,
+            device_offset: 3,
+        }
+    }
+}
+
 fn parse_input(input: &str) -> Result<Vec<u64>> {
     input
         .lines()
@@ -10,53 +32,98 @@ fn parse_input(input: &str) -> Result<Vec<u64>> {
         .collect::<Result<Vec<_>>>()
 }
 
-#[cached(
-    type = "UnboundCache<String, u64>",
-    create= "{ UnboundCache::new() }",
-    convert=r#"{format!("{}_{}", first, adapters.len())}"#
-)]
-fn num_arrangements(first: u64, adapters: &[u64]) -> u64 {
-    // dbg!(first);
-    // eprintln!("adapters: {:#?}", adapters);
-    if adapters.is_empty() {
-        return 1;
-    }
-
-    let candidates = adapters
-        .iter()
-        .enumerate()
-        .take_while(|(_i, v)| *v - first <= 3)
+/// Number of ways to chain adapters from `first` up to the last of `adapters`, which must be
+/// sorted. The ways to reach an adapter are the sum of the ways to reach each adapter it can
+/// follow; those are contiguous in the sorted list, so a single pass keeping a running sum over
+/// them is enough. Fails if the number doesn't fit in a `u64`.
+fn num_arrangements(spec: &AdapterSpec, first: u64, adapters: &[u64]) -> Result<u64> {
+    let joltages = std::iter::once(first)
+        .chain(adapters.iter().copied())
         .collect::<Vec<_>>();
-    let mut num = 0;
-    for (i, c) in &candidates {
-        num += num_arrangements(**c, &adapters[(*i as usize + 1)..]);
+    let mut ways = vec![0; joltages.len()];
+    ways[0] = 1;
+    // the adapters the current one can follow are `joltages[lowest..highest]`, and `in_reach`
+    // is the sum of their ways
+    let mut in_reach: u64 = 0;
+    let mut lowest = 0;
+    let mut highest = 0;
+    for i in 1..joltages.len() {
+        while joltages[i] - joltages[lowest] > spec.max_step {
+            if lowest < highest {
+                in_reach -= ways[lowest];
+            }
+            lowest += 1;
+        }
+        highest = highest.max(lowest);
+        while highest < i && joltages[i] - joltages[highest] >= spec.min_step {
+            in_reach = in_reach
+                .checked_add(ways[highest])
+                .ok_or_else(|| format_err!("too many arrangements to count"))?;
+            highest += 1;
+        }
+        ways[i] = in_reach;
     }
-    num
+
+    Ok(ways[joltages.len() - 1])
 }
 
-fn jolts(input: &str) -> Result<Vec<u64>> {
-    let mut adapters = parse_input(&input)?;
-    adapters.sort();
-    let max = adapters.iter().max().expect("no adapters!").clone();
-    adapters.insert(0, 0);
-    adapters.push(max + 3);
+/// The sorted joltages of the outlet, every adapter and the device.
+fn jolts(spec: &AdapterSpec, input: &str) -> Result<Vec<u64>> {
+    let mut adapters = parse_input(input)?;
+    adapters.sort_unstable();
+    let max = *adapters.last().ok_or_else(|| format_err!("no adapters!"))?;
+    ensure!(
+        adapters[0] >= spec.outlet,
+        "adapter rated {} is below the outlet",
+        adapters[0]
+    );
+    adapters.insert(0, spec.outlet);
+    adapters.push(max + spec.device_offset);
 
     Ok(adapters)
 }
 
+/// How many times each step size occurs when chaining every adapter. Fails if some step is
+/// outside what the spec allows.
+fn step_histogram(spec: &AdapterSpec, joltages: &[u64]) -> Result<BTreeMap<u64, usize>> {
+    let mut histogram = BTreeMap::new();
+    for w in joltages.windows(2) {
+        let step = w[1] - w[0];
+        ensure!(
+            step >= spec.min_step && step <= spec.max_step,
+            "can't chain {} jolts to {} jolts",
+            w[0],
+            w[1]
+        );
+        *histogram.entry(step).or_insert(0) += 1;
+    }
+
+    Ok(histogram)
+}
 
 fn main() -> Result<()> {
+    let args = env::args().collect::<Vec<_>>();
+    let default = AdapterSpec::default();
+    let spec = AdapterSpec {
+        min_step: advent20::flag_value(&args, "--min-step", default.min_step)?,
+        max_step: advent20::flag_value(&args, "--max-step", default.max_step)?,
+        outlet: advent20::flag_value(&args, "--outlet", default.outlet)?,
+        device_offset: advent20::flag_value(&args, "--device-offset", default.device_offset)?,
+    };
+
     let input = advent20::input_string()?;
 
-    let adapters = jolts(&input)?;
+    let adapters = jolts(&spec, &input)?;
 
-    let differences = adapters.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
-    let diff_1 = differences.iter().filter(|&&v| v == 1).count();
-    let diff_3 = differences.iter().filter(|&&v| v == 3).count();
+    let histogram = step_histogram(&spec, &adapters)?;
+    for (step, count) in &histogram {
+        println!("steps of {}: {}", step, count);
+    }
+    let count = |step| histogram.get(&step).copied().unwrap_or(0);
 
-    println!("part 1: {}", diff_1 * diff_3);
+    println!("part 1: {}", count(1) * count(3));
 
-    let num = num_arrangements(0, &adapters[1..]);
+    let num = num_arrangements(&spec, adapters[0], &adapters[1..])?;
     println!("part 2: {}", num);
 
     Ok(())
@@ -100,7 +167,86 @@ mod tests {
 10
 3
 ";
-        let adapters = jolts(input).unwrap();
-        assert_eq!(19208, num_arrangements(0, &adapters[1..]));
+        let adapters = jolts(&AdapterSpec::default(), input).unwrap();
+        assert_eq!(
+            19208,
+            num_arrangements(&AdapterSpec::default(), 0, &adapters[1..]).unwrap()
+        );
+        // no state is kept between calls
+        assert_eq!(
+            19208,
+            num_arrangements(&AdapterSpec::default(), 0, &adapters[1..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let adapters = jolts(
+            &AdapterSpec::default(),
+            "16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4",
+        )
+        .unwrap();
+        assert_eq!(
+            8,
+            num_arrangements(&AdapterSpec::default(), 0, &adapters[1..]).unwrap()
+        );
+
+        assert_eq!(
+            1,
+            num_arrangements(&AdapterSpec::default(), 0, &[]).unwrap()
+        );
+        assert_eq!(
+            0,
+            num_arrangements(&AdapterSpec::default(), 0, &[1, 5]).unwrap()
+        );
+        assert_eq!(
+            4,
+            num_arrangements(&AdapterSpec::default(), 0, &[1, 2, 3]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_spec() {
+        let input = "16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4";
+        let adapters = jolts(&AdapterSpec::default(), input).unwrap();
+        let histogram = step_histogram(&AdapterSpec::default(), &adapters).unwrap();
+        assert_eq!(
+            vec![(1, 7), (3, 5)],
+            histogram.into_iter().collect::<Vec<_>>()
+        );
+
+        // with steps of up to 4, 1 -> 5 is allowed too
+        let spec = AdapterSpec {
+            max_step: 4,
+            device_offset: 4,
+            ..AdapterSpec::default()
+        };
+        let adapters = jolts(&spec, input).unwrap();
+        assert_eq!(Some(&23), adapters.last());
+        let histogram = step_histogram(&spec, &adapters).unwrap();
+        assert_eq!(
+            vec![(1, 7), (3, 4), (4, 1)],
+            histogram.into_iter().collect::<Vec<_>>()
+        );
+
+        // without steps of 1, the adapters can't all be chained
+        let spec = AdapterSpec {
+            min_step: 2,
+            ..AdapterSpec::default()
+        };
+        let adapters = jolts(&spec, input).unwrap();
+        assert!(step_histogram(&spec, &adapters).is_err());
+        assert_eq!(0, num_arrangements(&spec, 0, &adapters[1..]).unwrap());
+        assert_eq!(1, num_arrangements(&spec, 0, &[2, 4, 7]).unwrap());
+
+        let spec = AdapterSpec {
+            outlet: 2,
+            ..AdapterSpec::default()
+        };
+        assert!(jolts(&spec, input).is_err());
+        assert_eq!(1, num_arrangements(&spec, 2, &[3]).unwrap());
+
+        let adapters = (1..=200).collect::<Vec<_>>();
+        assert!(num_arrangements(&AdapterSpec::default(), 0, &adapters).is_err());
     }
 }
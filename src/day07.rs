@@ -0,0 +1,702 @@
+//! Handy Haversacks: bag rules and the graph of which bags contain which.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    convert::TryFrom,
+    fmt,
+};
+
+use anyhow::{format_err, Result};
+use itertools::Itertools;
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::{digit1, space0, space1},
+    combinator::value,
+    combinator::{all_consuming, map, map_res, not, verify},
+    multi::{many1, separated_list1},
+    sequence::{delimited, terminated, tuple},
+    Finish, IResult,
+};
+use petgraph::{algo::tarjan_scc, dot::Dot, graphmap::DiGraphMap, Direction};
+use serde::Serialize;
+
+/// Whether the number of bags must agree with "bag"/"bags" in the rules.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum Plurality {
+    /// "1 bag", "2 bags", and "bags" everywhere else.
+    Strict,
+    /// "bag" and "bags" are interchangeable.
+    #[default]
+    Lenient,
+}
+
+fn word(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_alphabetic() || c == '-')(input)
+}
+
+/// The "bag" keyword. With strict plurality, `count` tells whether "bag" or "bags" is expected
+/// (no count meaning plural).
+fn bag_keyword<'a>(
+    plurality: Plurality,
+    count: Option<u8>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    move |input: &'a str| {
+        let keyword = match (plurality, count) {
+            (Plurality::Lenient, _) => alt((tag("bags"), tag("bag")))(input),
+            (Plurality::Strict, Some(1)) => tag("bag")(input),
+            (Plurality::Strict, _) => tag("bags")(input),
+        }?;
+        // make sure we matched the whole word, and not just a prefix of it
+        not(word)(keyword.0)?;
+
+        Ok(keyword)
+    }
+}
+
+/// A colour is any number of words up to the "bag" keyword. Words are separated by any amount
+/// of whitespace, which is normalized to a single space.
+fn colour(input: &str) -> IResult<&str, Colour> {
+    map(
+        many1(terminated(
+            verify(word, |w: &str| w != "bag" && w != "bags"),
+            space1,
+        )),
+        |words| Colour(words.join(" ")),
+    )(input)
+}
+
+fn contain_rule<'a>(plurality: Plurality) -> impl FnMut(&'a str) -> IResult<&'a str, ContainRule> {
+    move |input: &'a str| {
+        let (input, (num, _, colour)) =
+            tuple((map_res(digit1, |s: &str| s.parse::<u8>()), space1, colour))(input)?;
+        let (input, _) = bag_keyword(plurality, Some(num))(input)?;
+
+        Ok((input, ContainRule(num, colour)))
+    }
+}
+
+fn rule<'a>(plurality: Plurality) -> impl FnMut(&'a str) -> IResult<&'a str, Rule> {
+    map(
+        all_consuming(tuple((
+            space0,
+            colour,
+            bag_keyword(plurality, None),
+            delimited(space1, tag("contain"), space1),
+            alt((
+                value(
+                    Vec::new(),
+                    tuple((
+                        tag("no"),
+                        space1,
+                        tag("other"),
+                        space1,
+                        bag_keyword(plurality, None),
+                    )),
+                ),
+                separated_list1(delimited(space0, tag(","), space0), contain_rule(plurality)),
+            )),
+            delimited(space0, tag("."), space0),
+        ))),
+        |(_, colour, _, _, contain_rules, _)| Rule {
+            colour,
+            contains: contain_rules,
+        },
+    )
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Colour(String);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ContainRule(u8, Colour);
+
+#[derive(Debug)]
+pub struct Rule {
+    pub colour: Colour,
+    pub contains: Vec<ContainRule>,
+}
+
+impl Rule {
+    pub fn parse(input: &str, plurality: Plurality) -> Result<Rule> {
+        rule(plurality)(input)
+            .finish()
+            .map(|(_, rule)| rule)
+            .map_err(|e| format_err!("Failed to parse rule: {}", e))
+    }
+}
+
+impl TryFrom<&str> for Rule {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Rule::parse(value, Plurality::default())
+    }
+}
+
+/// Containment graph of the bag rules: an edge `a -> b` of weight `n` means that a bag of colour
+/// `a` must directly contain `n` bags of colour `b`.
+pub struct BagGraph<'a> {
+    colours: HashSet<&'a str>,
+    graph: DiGraphMap<&'a str, u8>,
+}
+
+impl<'a> BagGraph<'a> {
+    pub fn new(rules: &'a [Rule]) -> BagGraph<'a> {
+        let mut colours = HashSet::new();
+        let mut graph = DiGraphMap::new();
+        for rule in rules {
+            colours.insert(rule.colour.0.as_str());
+            graph.add_node(rule.colour.0.as_str());
+            for ContainRule(num, inner) in &rule.contains {
+                colours.insert(inner.0.as_str());
+                graph.add_edge(rule.colour.0.as_str(), inner.0.as_str(), *num);
+            }
+        }
+
+        BagGraph { colours, graph }
+    }
+
+    fn node(&self, colour: &str) -> Option<&'a str> {
+        self.colours.get(colour).copied()
+    }
+
+    /// Whether the colour appears anywhere in the rules.
+    pub fn has_colour(&self, colour: &str) -> bool {
+        self.colours.contains(colour)
+    }
+
+    /// Colours that can eventually contain a bag of the given colour.
+    pub fn containers_of(&self, colour: &str) -> HashSet<&'a str> {
+        self.reachable(colour, Direction::Incoming)
+    }
+
+    /// Colours that a bag of the given colour can eventually contain.
+    pub fn contents_of(&self, colour: &str) -> HashSet<&'a str> {
+        self.reachable(colour, Direction::Outgoing)
+    }
+
+    /// Breadth-first search from `colour`, following edges in the given direction. The start
+    /// colour itself is not part of the result.
+    fn reachable(&self, colour: &str, direction: Direction) -> HashSet<&'a str> {
+        let mut seen = HashSet::new();
+        let mut queue = self.node(colour).into_iter().collect::<VecDeque<_>>();
+        while let Some(current) = queue.pop_front() {
+            for next in self.graph.neighbors_directed(current, direction) {
+                if seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen.remove(colour);
+
+        seen
+    }
+
+    /// Total number of bags that a bag of the given colour must contain. Fails for an unknown
+    /// colour, if the colour (eventually) contains itself, since it would then need an infinite
+    /// number of bags, or if there are too many bags to count.
+    pub fn count_inside(&self, colour: &str) -> Result<u64> {
+        match self.node(colour) {
+            Some(node) => self.count_inside_cached(node, &mut HashMap::new(), &mut HashSet::new()),
+            None => Err(format_err!("unknown colour: {}", colour)),
+        }
+    }
+
+    fn count_inside_cached(
+        &self,
+        colour: &'a str,
+        cache: &mut HashMap<&'a str, u64>,
+        in_progress: &mut HashSet<&'a str>,
+    ) -> Result<u64> {
+        if let Some(count) = cache.get(colour) {
+            return Ok(*count);
+        }
+        if !in_progress.insert(colour) {
+            return Err(format_err!("{} bags contain themselves", colour));
+        }
+
+        let mut count = 0u64;
+        for (_, inner, num) in self.graph.edges(colour) {
+            let inside = self.count_inside_cached(inner, cache, in_progress)?;
+            count = inside
+                .checked_add(1)
+                .and_then(|n| n.checked_mul(*num as u64))
+                .and_then(|n| n.checked_add(count))
+                .ok_or_else(|| format_err!("too many bags to count inside {} bags", colour))?;
+        }
+        in_progress.remove(colour);
+        cache.insert(colour, count);
+
+        Ok(count)
+    }
+
+    /// Groups of colours that contain each other, i.e. strongly connected components with more
+    /// than one colour, or a single colour that directly contains itself.
+    pub fn cycles(&self) -> Vec<Vec<&'a str>> {
+        tarjan_scc(&self.graph)
+            .into_iter()
+            .filter(|scc| scc.len() > 1 || self.graph.contains_edge(scc[0], scc[0]))
+            .collect()
+    }
+
+    /// Shortest chain of bags leading from `from` to `to`. Each step is a colour with the number
+    /// of bags of that colour directly inside the previous one; the first step is `from` itself,
+    /// with a count of 1.
+    pub fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<(&'a str, u8)>> {
+        let from = self.node(from)?;
+        let to = self.node(to)?;
+
+        let mut previous: HashMap<&'a str, (&'a str, u8)> = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(from);
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                break;
+            }
+            for (_, next, num) in self.graph.edges(current) {
+                if next != from && !previous.contains_key(next) {
+                    previous.insert(next, (current, *num));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut path = vec![];
+        let mut current = to;
+        while current != from {
+            let (prev, num) = previous.get(current)?;
+            path.push((current, *num));
+            current = prev;
+        }
+        path.push((from, 1));
+        path.reverse();
+
+        Some(path)
+    }
+
+    /// How deeply bags are nested inside a bag of the given colour, i.e. 0 for a bag that
+    /// contains no other bags. Fails for an unknown colour, or if the colour (eventually)
+    /// contains itself.
+    pub fn depth(&self, colour: &str) -> Result<usize> {
+        match self.node(colour) {
+            Some(node) => self.depth_cached(node, &mut HashMap::new(), &mut HashSet::new()),
+            None => Err(format_err!("unknown colour: {}", colour)),
+        }
+    }
+
+    fn depth_cached(
+        &self,
+        colour: &'a str,
+        cache: &mut HashMap<&'a str, usize>,
+        in_progress: &mut HashSet<&'a str>,
+    ) -> Result<usize> {
+        if let Some(depth) = cache.get(colour) {
+            return Ok(*depth);
+        }
+        if !in_progress.insert(colour) {
+            return Err(format_err!("{} bags contain themselves", colour));
+        }
+
+        let mut depth = 0;
+        for inner in self.graph.neighbors(colour) {
+            depth = depth.max(1 + self.depth_cached(inner, cache, in_progress)?);
+        }
+        in_progress.remove(colour);
+        cache.insert(colour, depth);
+
+        Ok(depth)
+    }
+
+    /// The whole graph, or only the part of it "around" a colour: that colour, the colours that
+    /// can contain it and the colours it can contain, with the edges along those paths.
+    pub fn subgraph(&self, around: Option<&str>) -> DiGraphMap<&'a str, u8> {
+        let colour = match around.and_then(|colour| self.node(colour)) {
+            Some(colour) => colour,
+            None if around.is_some() => return DiGraphMap::new(),
+            None => return self.graph.clone(),
+        };
+
+        let mut containers = self.containers_of(colour);
+        containers.insert(colour);
+        let mut contents = self.contents_of(colour);
+        contents.insert(colour);
+
+        let mut subgraph = DiGraphMap::new();
+        subgraph.add_node(colour);
+        for (outer, inner, num) in self.graph.all_edges() {
+            if (containers.contains(outer) && containers.contains(inner))
+                || (contents.contains(outer) && contents.contains(inner))
+            {
+                subgraph.add_edge(outer, inner, *num);
+            }
+        }
+
+        subgraph
+    }
+
+    /// Graphviz representation of the (optionally restricted) graph, with edges labelled by the
+    /// number of bags.
+    pub fn to_dot(&self, around: Option<&str>) -> String {
+        format!("{}", Dot::new(&self.subgraph(around)))
+    }
+
+    /// JSON object mapping each colour to the list of colours (and counts) it directly contains.
+    pub fn to_json(&self, around: Option<&str>) -> Result<String> {
+        let subgraph = self.subgraph(around);
+        let adjacency = subgraph
+            .nodes()
+            .map(|colour| {
+                let mut edges = subgraph
+                    .edges(colour)
+                    .map(|(_, inner, count)| JsonEdge {
+                        colour: inner,
+                        count: *count,
+                    })
+                    .collect::<Vec<_>>();
+                edges.sort_unstable_by_key(|edge| edge.colour);
+                (colour, edges)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        Ok(serde_json::to_string_pretty(&adjacency)?)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonEdge<'a> {
+    colour: &'a str,
+    count: u8,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RuleError {
+    /// A colour is used on `line` but has no rule of its own.
+    UndefinedColour { line: usize, colour: String },
+    /// A colour already defined on `first_line` is defined again on `line`.
+    DuplicateColour {
+        line: usize,
+        first_line: usize,
+        colour: String,
+    },
+    /// The rules on these lines contain each other, so they would need infinitely many bags.
+    Cycle {
+        lines: Vec<usize>,
+        colours: Vec<String>,
+    },
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::UndefinedColour { line, colour } => {
+                write!(f, "line {}: {} bags are never defined", line, colour)
+            }
+            RuleError::DuplicateColour {
+                line,
+                first_line,
+                colour,
+            } => write!(
+                f,
+                "line {}: {} bags are already defined on line {}",
+                line, colour, first_line
+            ),
+            RuleError::Cycle { lines, colours } => write!(
+                f,
+                "lines {}: {} bags contain each other",
+                lines.iter().join(", "),
+                colours.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// Check a set of rules for undefined or duplicate colours, and for cycles. Line numbers are
+/// the (1-based) position of each rule in `rules`, i.e. the line it was parsed from.
+pub fn validate_rules(rules: &[Rule]) -> Vec<RuleError> {
+    let mut errors = Vec::new();
+
+    let mut defined: HashMap<&str, usize> = HashMap::new();
+    for (i, rule) in rules.iter().enumerate() {
+        if let Some(first_line) = defined.get(rule.colour.0.as_str()) {
+            errors.push(RuleError::DuplicateColour {
+                line: i + 1,
+                first_line: *first_line,
+                colour: rule.colour.0.clone(),
+            });
+        } else {
+            defined.insert(rule.colour.0.as_str(), i + 1);
+        }
+    }
+
+    for (i, rule) in rules.iter().enumerate() {
+        for ContainRule(_, inner) in &rule.contains {
+            if !defined.contains_key(inner.0.as_str()) {
+                errors.push(RuleError::UndefinedColour {
+                    line: i + 1,
+                    colour: inner.0.clone(),
+                });
+            }
+        }
+    }
+
+    for mut cycle in BagGraph::new(rules).cycles() {
+        cycle.sort_unstable_by_key(|colour| defined[colour]);
+        errors.push(RuleError::Cycle {
+            lines: cycle.iter().map(|colour| defined[colour]).collect(),
+            colours: cycle.iter().map(|colour| colour.to_string()).collect(),
+        });
+    }
+
+    errors
+}
+
+/// The example rules from the puzzle description: 4 colours can eventually contain a shiny gold
+/// bag, which must itself contain 32 other bags.
+pub const EXAMPLE: &str = r#"light red bags contain 1 bright white bag, 2 muted yellow bags.
+dark orange bags contain 3 bright white bags, 4 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
+dark olive bags contain 3 faded blue bags, 4 dotted black bags.
+vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
+faded blue bags contain no other bags.
+dotted black bags contain no other bags."#;
+
+pub fn parse_rules(input: &str) -> Result<Vec<Rule>> {
+    parse_rules_with(input, Plurality::default())
+}
+
+pub fn parse_rules_with(input: &str, plurality: Plurality) -> Result<Vec<Rule>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            Rule::parse(line, plurality).map_err(|e| format_err!("line {}: {}", i + 1, e))
+        })
+        .collect::<Result<Vec<Rule>>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contain_rule() {
+        assert_eq!(
+            contain_rule(Plurality::Lenient)("3 muted magenta bag"),
+            Ok(("", ContainRule(3, Colour("muted magenta".to_string()))))
+        );
+        assert!(contain_rule(Plurality::Strict)("3 muted magenta bag").is_err());
+        assert_eq!(
+            contain_rule(Plurality::Strict)("1 muted magenta bag"),
+            Ok(("", ContainRule(1, Colour("muted magenta".to_string()))))
+        );
+        assert!(contain_rule(Plurality::Strict)("1 muted magenta bags").is_err());
+    }
+
+    #[test]
+    fn test_colour_grammar() {
+        let colour_of = |input: &str| Rule::try_from(input).map(|rule| rule.colour.0);
+
+        assert_eq!("red", colour_of("red bags contain no other bags.").unwrap());
+        assert_eq!(
+            "dark shiny gold",
+            colour_of("dark shiny gold bags contain no other bags.").unwrap()
+        );
+        assert_eq!(
+            "blue-green",
+            colour_of("blue-green bags contain no other bags.").unwrap()
+        );
+        assert_eq!(
+            "light red",
+            colour_of("  light   red  bags   contain  no   other bags .  ").unwrap()
+        );
+
+        let rule =
+            Rule::try_from("red bags contain 1 very dark-blue bag,2 pale  green bags.").unwrap();
+        assert_eq!(
+            vec![
+                ContainRule(1, Colour("very dark-blue".to_string())),
+                ContainRule(2, Colour("pale green".to_string()))
+            ],
+            rule.contains
+        );
+
+        assert!(Rule::try_from("bags contain no other bags.").is_err());
+        assert!(Rule::try_from("red bagsy bags contain no other bags.").is_ok());
+        assert!(Rule::try_from("red bags contain 2 blue bags. trailing").is_err());
+        assert!(Rule::try_from("red bags contain 2 bags.").is_err());
+    }
+
+    #[test]
+    fn test_plurality() {
+        let strict = |input: &str| Rule::parse(input, Plurality::Strict);
+
+        assert!(strict("red bags contain 1 blue bag, 2 green bags.").is_ok());
+        assert!(strict("red bags contain 1 blue bags.").is_err());
+        assert!(strict("red bags contain 2 blue bag.").is_err());
+        assert!(strict("red bag contain 2 blue bags.").is_err());
+        assert!(strict("red bags contain no other bag.").is_err());
+        assert!(strict("red bags contain 0 blue bags.").is_ok());
+
+        assert!(Rule::parse("red bag contain 2 blue bag.", Plurality::Lenient).is_ok());
+        assert!(
+            parse_rules_with("red bags contain 1 blue bags.", Plurality::Strict)
+                .unwrap_err()
+                .to_string()
+                .starts_with("line 1:")
+        );
+    }
+
+    #[test]
+    fn test_path_and_depth() {
+        let rules = parse_rules(EXAMPLE).unwrap();
+        let graph = BagGraph::new(&rules);
+
+        assert_eq!(
+            Some(vec![
+                ("light red", 1),
+                ("bright white", 1),
+                ("shiny gold", 1),
+                ("dark olive", 1)
+            ]),
+            graph.shortest_path("light red", "dark olive")
+        );
+        assert_eq!(
+            Some(vec![("muted yellow", 1), ("faded blue", 9)]),
+            graph.shortest_path("muted yellow", "faded blue")
+        );
+        assert_eq!(
+            Some(vec![("shiny gold", 1)]),
+            graph.shortest_path("shiny gold", "shiny gold")
+        );
+        assert_eq!(None, graph.shortest_path("shiny gold", "light red"));
+        assert_eq!(None, graph.shortest_path("shiny gold", "unknown colour"));
+
+        assert_eq!(4, graph.depth("light red").unwrap());
+        assert_eq!(2, graph.depth("shiny gold").unwrap());
+        assert_eq!(0, graph.depth("faded blue").unwrap());
+        assert!(graph.depth("shiny gld").is_err());
+    }
+
+    #[test]
+    fn test_export() {
+        let input = r#"light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag.
+dark olive bags contain no other bags.
+faded blue bags contain no other bags.
+dotted black bags contain 3 faded blue bags."#;
+
+        let rules = parse_rules(input).unwrap();
+        let graph = BagGraph::new(&rules);
+
+        let subgraph = graph.subgraph(Some("bright white"));
+        let mut nodes = subgraph.nodes().collect::<Vec<_>>();
+        nodes.sort_unstable();
+        assert_eq!(
+            vec!["bright white", "dark olive", "light red", "shiny gold"],
+            nodes
+        );
+        assert_eq!(3, subgraph.edge_count());
+        assert_eq!(0, graph.subgraph(Some("unknown colour")).node_count());
+        assert_eq!(7, graph.subgraph(None).node_count());
+
+        let dot = graph.to_dot(Some("bright white"));
+        assert!(dot.starts_with("digraph {"));
+        assert!(dot.contains("[ label = \"light red\" ]"));
+        assert!(dot.contains("[ label = \"1\" ]"));
+        assert!(!dot.contains("muted yellow"));
+
+        let json = graph.to_json(Some("shiny gold")).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "light red": [
+                    { "colour": "bright white", "count": 1 },
+                    { "colour": "muted yellow", "count": 2 },
+                ],
+                "bright white": [{ "colour": "shiny gold", "count": 1 }],
+                "muted yellow": [{ "colour": "shiny gold", "count": 2 }],
+                "shiny gold": [{ "colour": "dark olive", "count": 1 }],
+                "dark olive": [],
+            }),
+            value
+        );
+    }
+
+    #[test]
+    fn test_validate_rules() {
+        let input = r#"light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+shiny gold bags contain 1 light red bag.
+muted yellow bags contain no other bags.
+dotted black bags contain 2 dotted black bags.
+muted yellow bags contain 3 faded blue bags."#;
+
+        let rules = parse_rules(input).unwrap();
+        let errors = validate_rules(&rules);
+
+        assert_eq!(
+            vec![
+                RuleError::DuplicateColour {
+                    line: 6,
+                    first_line: 4,
+                    colour: "muted yellow".to_string()
+                },
+                RuleError::UndefinedColour {
+                    line: 6,
+                    colour: "faded blue".to_string()
+                },
+            ],
+            errors[..2]
+        );
+        assert_eq!(4, errors.len());
+        assert!(errors.contains(&RuleError::Cycle {
+            lines: vec![1, 2, 3],
+            colours: vec![
+                "light red".to_string(),
+                "bright white".to_string(),
+                "shiny gold".to_string()
+            ]
+        }));
+        assert!(errors.contains(&RuleError::Cycle {
+            lines: vec![5],
+            colours: vec!["dotted black".to_string()]
+        }));
+        assert_eq!(
+            "lines 1, 2, 3: light red, bright white, shiny gold bags contain each other",
+            errors
+                .iter()
+                .find(|e| matches!(e, RuleError::Cycle { lines, .. } if lines.len() == 3))
+                .unwrap()
+                .to_string()
+        );
+
+        let graph = BagGraph::new(&rules);
+        assert!(graph.count_inside("shiny gold").is_err());
+        assert!(graph.count_inside("dotted black").is_err());
+        assert_eq!(0, graph.count_inside("faded blue").unwrap());
+        assert!(graph.count_inside("shiny gld").is_err());
+    }
+
+    #[test]
+    fn test_count_overflow() {
+        // 255^10 bags don't fit in a u64
+        let letters = "abcdefghijk".chars().collect::<Vec<_>>();
+        let mut input = letters
+            .windows(2)
+            .map(|w| format!("{0}{0} x bags contain 255 {1}{1} x bags.\n", w[0], w[1]))
+            .collect::<String>();
+        input.push_str("kk x bags contain no other bags.");
+        let rules = parse_rules(&input).unwrap();
+        let graph = BagGraph::new(&rules);
+
+        assert_eq!(255, graph.count_inside("jj x").unwrap());
+        assert!(graph.count_inside("aa x").is_err());
+    }
+}
@@ -0,0 +1,531 @@
+//! Handheld Halting: the handheld game console's instruction set and a small VM to run it.
+
+use std::collections::HashMap;
+
+use anyhow::{format_err, Result};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    character::complete::digit1,
+    combinator::{map, map_res},
+    sequence::tuple,
+    Finish, IResult,
+};
+use serde::{Deserialize, Serialize};
+
+use trace::Trace;
+
+pub mod asm;
+pub mod cfg;
+pub mod fast;
+pub mod search;
+pub mod trace;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Acc,
+    Jmp,
+    Nop,
+    /// Extended instruction set: multiply the accumulator, wrapping around on overflow.
+    Mul,
+    /// Extended instruction set: jump if the accumulator is zero.
+    Jz,
+    /// Extended instruction set: stop the program, as if it had run past its last instruction.
+    Hlt,
+}
+
+impl Op {
+    /// Whether the operation is only part of the extended instruction set.
+    pub fn is_extended(&self) -> bool {
+        matches!(self, Op::Mul | Op::Jz | Op::Hlt)
+    }
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Self::Acc => "acc",
+                Self::Jmp => "jmp",
+                Self::Nop => "nop",
+                Self::Mul => "mul",
+                Self::Jz => "jz",
+                Self::Hlt => "hlt",
+            }
+        )
+    }
+}
+
+impl std::str::FromStr for Op {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "acc" => Ok(Op::Acc),
+            "jmp" => Ok(Op::Jmp),
+            "nop" => Ok(Op::Nop),
+            "mul" => Ok(Op::Mul),
+            "jz" => Ok(Op::Jz),
+            "hlt" => Ok(Op::Hlt),
+            _ => Err(format_err!("unknown operation: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Inst {
+    pub op: Op,
+    pub offset: i32,
+}
+
+impl std::fmt::Display for Inst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:+}", self.op, self.offset)
+    }
+}
+
+fn parse_inst(input: &str) -> IResult<&str, Inst> {
+    map(
+        tuple((
+            alt((tag("acc"), tag("jmp"), tag("nop"))),
+            tag(" "),
+            alt((char('-'), char('+'))),
+            map_res(digit1, |s: &str| s.parse::<i32>()),
+        )),
+        |(op, _, sign, mut offset)| {
+            if sign == '-' {
+                offset *= -1;
+            }
+            let op = match op {
+                "acc" => Op::Acc,
+                "jmp" => Op::Jmp,
+                "nop" => Op::Nop,
+                _ => unreachable!(),
+            };
+            Inst { op, offset }
+        },
+    )(input)
+}
+
+pub fn parse_program(input: &str) -> Result<Vec<Inst>> {
+    input
+        .lines()
+        .map(|line| {
+            parse_inst(line)
+                .finish()
+                .map(|v| v.1)
+                .map_err(|e| format_err!("Failed to parse instruction: {}", e))
+        })
+        .collect::<Result<Vec<_>>>()
+}
+
+/// The example program from the puzzle description. It loops with 5 in the accumulator, and
+/// terminates with 8 once the `jmp -4` at address 7 is flipped.
+pub const EXAMPLE: &str = "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6";
+
+/// Snapshot of the console's registers.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct State {
+    pub acc: i32,
+    pub pc: i32,
+}
+
+/// Why the console stopped running.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ExitReason {
+    /// The program tried to execute the instruction right after its last one.
+    Terminated,
+    /// The instruction at `pc` was about to be executed once more than allowed, which is a
+    /// second time unless [`Console::set_max_visits`] says otherwise.
+    InfiniteLoop { acc: i32, pc: i32 },
+    /// The program jumped somewhere other than one of its instructions or the one right after
+    /// the end.
+    OutOfBounds { pc: i32 },
+}
+
+/// The handheld game console, running a program until it terminates or an instruction is about
+/// to be executed twice.
+#[derive(Debug, Clone)]
+pub struct Console {
+    prg: Vec<Inst>,
+    state: State,
+    /// How many times each instruction has been executed.
+    visits: HashMap<i32, usize>,
+    max_visits: usize,
+    trace: Option<Trace>,
+}
+
+impl Console {
+    pub fn new(prg: Vec<Inst>) -> Console {
+        Console {
+            prg,
+            state: State::default(),
+            visits: HashMap::new(),
+            max_visits: 1,
+            trace: None,
+        }
+    }
+
+    /// Let each instruction be executed up to `max_visits` times before stopping with
+    /// [`ExitReason::InfiniteLoop`], instead of just once. Going round a loop a few times is
+    /// mostly useful when tracing.
+    pub fn set_max_visits(&mut self, max_visits: usize) {
+        self.max_visits = max_visits;
+    }
+
+    /// Start recording every executed instruction.
+    pub fn enable_trace(&mut self) {
+        self.trace.get_or_insert_with(Trace::default);
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    pub fn program(&self) -> &[Inst] {
+        &self.prg
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Replace the instruction at `addr`. The execution state is kept as it is.
+    pub fn patch(&mut self, addr: usize, inst: Inst) -> Result<Inst> {
+        let len = self.prg.len();
+        let slot = self
+            .prg
+            .get_mut(addr)
+            .ok_or_else(|| format_err!("no instruction at {} (program has {})", addr, len))?;
+
+        Ok(std::mem::replace(slot, inst))
+    }
+
+    /// Go back to the start of the program, with a clear accumulator and no visited
+    /// instructions. Patches are kept.
+    pub fn reset(&mut self) {
+        self.state = State::default();
+        self.visits.clear();
+        if self.trace.is_some() {
+            self.trace = Some(Trace::default());
+        }
+    }
+
+    /// Check whether the console should stop before executing the current instruction.
+    pub fn exit_reason(&self) -> Option<ExitReason> {
+        let State { acc, pc } = self.state;
+        if pc == self.prg.len() as i32 {
+            Some(ExitReason::Terminated)
+        } else if pc < 0 || pc > self.prg.len() as i32 {
+            Some(ExitReason::OutOfBounds { pc })
+        } else if self.visits.get(&pc).copied().unwrap_or(0) >= self.max_visits {
+            Some(ExitReason::InfiniteLoop { acc, pc })
+        } else {
+            None
+        }
+    }
+
+    /// Execute the current instruction, unless the console has to stop, in which case the reason
+    /// is returned and the state is left untouched.
+    pub fn step(&mut self) -> Option<ExitReason> {
+        if let Some(reason) = self.exit_reason() {
+            return Some(reason);
+        }
+
+        let State { acc, pc } = self.state;
+        *self.visits.entry(pc).or_insert(0) += 1;
+        let i = &self.prg[pc as usize];
+        self.state = execute(i, self.state, self.prg.len() as i32);
+        if let Some(trace) = &mut self.trace {
+            trace.record(pc, *i, acc, self.state.acc);
+        }
+
+        None
+    }
+
+    /// Run the program until it stops.
+    pub fn run(&mut self) -> ExitReason {
+        loop {
+            if let Some(reason) = self.step() {
+                break reason;
+            }
+        }
+    }
+}
+
+/// The address `offset` away from `addr`. A target beyond the range of `i32` can't be inside the
+/// program, so it is clamped to the nearest bound and reported as out of bounds like any other.
+fn jump_target(addr: i32, offset: i32) -> i32 {
+    addr.saturating_add(offset)
+}
+
+/// The state of the console after executing `inst`, in a program of `len` instructions. The
+/// accumulator is a 32-bit register: `acc` and `mul` wrap around when they overflow.
+fn execute(inst: &Inst, state: State, len: i32) -> State {
+    let State { acc, pc } = state;
+    match inst.op {
+        Op::Acc => State {
+            acc: acc.wrapping_add(inst.offset),
+            pc: pc + 1,
+        },
+        Op::Mul => State {
+            acc: acc.wrapping_mul(inst.offset),
+            pc: pc + 1,
+        },
+        Op::Jmp => State {
+            acc,
+            pc: jump_target(pc, inst.offset),
+        },
+        Op::Jz if acc == 0 => State {
+            acc,
+            pc: jump_target(pc, inst.offset),
+        },
+        Op::Nop | Op::Jz => State { acc, pc: pc + 1 },
+        Op::Hlt => State { acc, pc: len },
+    }
+}
+
+/// The addresses the console may go to after executing `inst` at `addr`, in a program of `len`
+/// instructions. Only `jz` has two of them.
+fn successors(inst: &Inst, addr: i32, len: i32) -> Vec<i32> {
+    match inst.op {
+        Op::Acc | Op::Mul | Op::Nop => vec![addr + 1],
+        Op::Jmp => vec![jump_target(addr, inst.offset)],
+        Op::Jz => vec![jump_target(addr, inst.offset), addr + 1],
+        Op::Hlt => vec![len],
+    }
+}
+
+/// Swap a `jmp` for a `nop` or the other way round. Other instructions are left as they are.
+pub fn flip(inst: &Inst) -> Inst {
+    let op = match inst.op {
+        Op::Jmp => Op::Nop,
+        Op::Nop => Op::Jmp,
+        op => op,
+    };
+
+    Inst { op, ..*inst }
+}
+
+/// Addresses from which the program eventually terminates, i.e. reaches the address right after
+/// its last instruction. The end address itself is included. With the extended instruction set,
+/// a `jz` counts as terminating if either of its branches does.
+pub fn terminating_addrs(prg: &[Inst]) -> Vec<bool> {
+    let len = prg.len() as i32;
+    let mut predecessors = vec![Vec::new(); prg.len() + 1];
+    for (addr, inst) in prg.iter().enumerate() {
+        for next in successors(inst, addr as i32, len) {
+            if (0..=len).contains(&next) {
+                predecessors[next as usize].push(addr);
+            }
+        }
+    }
+
+    let mut terminating = vec![false; prg.len() + 1];
+    terminating[prg.len()] = true;
+    let mut stack = vec![prg.len()];
+    while let Some(addr) = stack.pop() {
+        for &pred in &predecessors[addr] {
+            if !terminating[pred] {
+                terminating[pred] = true;
+                stack.push(pred);
+            }
+        }
+    }
+
+    terminating
+}
+
+/// A single `jmp`/`nop` flip that makes a program terminate.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Repair {
+    pub addr: usize,
+    pub original: Inst,
+    pub patched: Inst,
+    /// Value of the accumulator once the repaired program terminates.
+    pub acc: i32,
+}
+
+/// Find the instruction to flip to make the program terminate, in linear time: first compute
+/// the addresses from which the program terminates, then follow the original execution path
+/// until an instruction is found whose flipped version leads to one of those addresses. A program
+/// that already terminates needs no repair, and gets none.
+pub fn repair(prg: &[Inst]) -> Option<Repair> {
+    let terminating = terminating_addrs(prg);
+    let len = prg.len() as i32;
+    if terminating[0] && Console::new(prg.to_vec()).run() == ExitReason::Terminated {
+        return None;
+    }
+
+    let mut visited = vec![false; prg.len()];
+    let mut state = State::default();
+    while (0..len).contains(&state.pc) && !visited[state.pc as usize] {
+        let addr = state.pc as usize;
+        visited[addr] = true;
+        let inst = &prg[addr];
+        let patched = flip(inst);
+        let next = execute(&patched, state, len).pc;
+        if patched != *inst && (0..=len).contains(&next) && terminating[next as usize] {
+            let mut patched_prg = prg.to_vec();
+            patched_prg[addr] = patched;
+            let mut console = Console::new(patched_prg);
+            // with `jz` in the program, reaching a terminating address doesn't guarantee
+            // termination, so keep looking if the repaired program still doesn't terminate
+            if console.run() == ExitReason::Terminated {
+                return Some(Repair {
+                    addr,
+                    original: *inst,
+                    patched,
+                    acc: console.state().acc,
+                });
+            }
+        }
+        state = execute(inst, state, len);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(input: &str) -> Vec<Inst> {
+        parse_program(input).unwrap()
+    }
+
+    #[test]
+    fn test_exit_reasons() {
+        let mut console = Console::new(program(EXAMPLE));
+        assert_eq!(ExitReason::InfiniteLoop { acc: 5, pc: 1 }, console.run());
+        assert_eq!(State { acc: 5, pc: 1 }, console.state());
+
+        let mut console = Console::new(program("acc +2\nnop -4\nacc +3"));
+        assert_eq!(ExitReason::Terminated, console.run());
+        assert_eq!(State { acc: 5, pc: 3 }, console.state());
+
+        let mut console = Console::new(program("acc +1\njmp +5\nacc +3"));
+        assert_eq!(ExitReason::OutOfBounds { pc: 6 }, console.run());
+
+        let mut console = Console::new(program("jmp -1"));
+        assert_eq!(ExitReason::OutOfBounds { pc: -1 }, console.run());
+
+        let mut console = Console::new(program("nop +0\njmp +2147483647"));
+        assert_eq!(ExitReason::OutOfBounds { pc: i32::MAX }, console.run());
+    }
+
+    #[test]
+    fn test_overflow_wraps() {
+        let mut console = Console::new(program("acc +2000000000\nacc +2000000000"));
+        assert_eq!(ExitReason::Terminated, console.run());
+        assert_eq!(
+            2_000_000_000i32.wrapping_add(2_000_000_000),
+            console.state().acc
+        );
+
+        let prg = asm::assemble("acc +70000\nmul 70000", asm::Dialect::Extended).unwrap();
+        let mut console = Console::new(prg);
+        assert_eq!(ExitReason::Terminated, console.run());
+        assert_eq!(70_000i32.wrapping_mul(70_000), console.state().acc);
+    }
+
+    #[test]
+    fn test_patch_and_reset() {
+        let mut console = Console::new(program("acc +3\njmp -1"));
+        assert_eq!(ExitReason::InfiniteLoop { acc: 3, pc: 0 }, console.run());
+
+        let old = console
+            .patch(
+                1,
+                Inst {
+                    op: Op::Nop,
+                    offset: -1,
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            Inst {
+                op: Op::Jmp,
+                offset: -1
+            },
+            old
+        );
+        assert!(console.patch(2, old).is_err());
+
+        console.reset();
+        assert_eq!(State::default(), console.state());
+        assert_eq!(ExitReason::Terminated, console.run());
+        assert_eq!(3, console.state().acc);
+    }
+
+    #[test]
+    fn test_step() {
+        let mut console = Console::new(program("acc +3\njmp -1"));
+        assert_eq!(None, console.step());
+        assert_eq!(State { acc: 3, pc: 1 }, console.state());
+        assert_eq!(None, console.step());
+        assert_eq!(State { acc: 3, pc: 0 }, console.state());
+        assert_eq!(
+            Some(ExitReason::InfiniteLoop { acc: 3, pc: 0 }),
+            console.step()
+        );
+        // stopped consoles stay put
+        assert_eq!(
+            Some(ExitReason::InfiniteLoop { acc: 3, pc: 0 }),
+            console.step()
+        );
+        assert_eq!(State { acc: 3, pc: 0 }, console.state());
+    }
+
+    #[test]
+    fn test_repair() {
+        let prg = program(EXAMPLE);
+        assert_eq!(
+            Some(Repair {
+                addr: 7,
+                original: Inst {
+                    op: Op::Jmp,
+                    offset: -4
+                },
+                patched: Inst {
+                    op: Op::Nop,
+                    offset: -4
+                },
+                acc: 8
+            }),
+            repair(&prg)
+        );
+
+        // the flip can also turn a `nop` into a `jmp`
+        let prg = program("nop +3\nacc +1\njmp -2\nacc +5");
+        assert_eq!(Some((0, 5)), repair(&prg).map(|r| (r.addr, r.acc)));
+
+        // no single flip can make this one terminate
+        let prg = program("acc +1\njmp -1\njmp -1");
+        assert_eq!(None, repair(&prg));
+
+        // nothing to repair
+        assert_eq!(None, repair(&program("nop +1\nacc +1")));
+
+        let prg = program("nop +0\njmp +2147483647");
+        assert_eq!(vec![false, false, true], terminating_addrs(&prg));
+        assert_eq!(Some((1, 0)), repair(&prg).map(|r| (r.addr, r.acc)));
+    }
+
+    #[test]
+    fn test_terminating_addrs() {
+        let prg = program(EXAMPLE);
+        assert_eq!(
+            vec![false, false, false, false, false, false, false, false, true, true],
+            terminating_addrs(&prg)
+        );
+    }
+}
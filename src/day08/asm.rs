@@ -0,0 +1,246 @@
+//! A small assembler for console programs, with comments, labels and an optional extended
+//! instruction set.
+//!
+//! ```text
+//! # leave the accumulator at 42
+//!         jz start        # extended dialect only
+//!         hlt
+//! start:  acc +21
+//!         mul 2
+//! ```
+
+use std::collections::HashMap;
+
+use anyhow::{format_err, Result};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{alpha1, alphanumeric1, char, digit1, one_of, space0, space1},
+    combinator::{all_consuming, map, map_res, opt, recognize},
+    multi::many0,
+    sequence::{pair, preceded, terminated, tuple},
+    Finish, IResult,
+};
+
+use super::{Inst, Op};
+
+/// Which instructions the assembler accepts.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum Dialect {
+    /// `acc`, `jmp` and `nop`, as understood by the handheld console.
+    #[default]
+    Standard,
+    /// The standard instructions, plus `mul`, `jz` and `hlt`.
+    Extended,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Operand<'a> {
+    Value(i32),
+    Label(&'a str),
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+struct Line<'a> {
+    label: Option<&'a str>,
+    inst: Option<(&'a str, Option<Operand<'a>>)>,
+}
+
+fn identifier(input: &str) -> IResult<&str, &str> {
+    recognize(pair(
+        alt((alpha1, tag("_"))),
+        many0(alt((alphanumeric1, tag("_")))),
+    ))(input)
+}
+
+fn number(input: &str) -> IResult<&str, i32> {
+    map_res(recognize(pair(opt(one_of("+-")), digit1)), |s: &str| {
+        s.parse::<i32>()
+    })(input)
+}
+
+fn operand(input: &str) -> IResult<&str, Operand<'_>> {
+    alt((map(number, Operand::Value), map(identifier, Operand::Label)))(input)
+}
+
+fn line(input: &str) -> IResult<&str, Line<'_>> {
+    map(
+        all_consuming(tuple((
+            space0,
+            opt(terminated(identifier, tuple((space0, char(':'), space0)))),
+            opt(pair(alpha1, opt(preceded(space1, operand)))),
+            space0,
+        ))),
+        |(_, label, inst, _)| Line { label, inst },
+    )(input)
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find('#') {
+        Some(pos) => &line[..pos],
+        None => line,
+    }
+}
+
+/// Assemble a program. Labels refer to the address of the next instruction, so a label after the
+/// last instruction can be used to jump to the end of the program.
+pub fn assemble(source: &str, dialect: Dialect) -> Result<Vec<Inst>> {
+    let mut lines = Vec::new();
+    let mut labels = HashMap::new();
+    let mut addr = 0;
+    for (i, text) in source.lines().enumerate() {
+        let (_, line) = line(strip_comment(text))
+            .finish()
+            .map_err(|e| format_err!("line {}: syntax error: {:?}", i + 1, e.input))?;
+        if let Some(label) = line.label {
+            if labels.insert(label, addr).is_some() {
+                return Err(format_err!("line {}: duplicate label {}", i + 1, label));
+            }
+        }
+        if let Some(inst) = line.inst {
+            lines.push((i + 1, addr, inst));
+            addr += 1;
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|(line, addr, (op, operand))| {
+            let op = op
+                .parse::<Op>()
+                .map_err(|e| format_err!("line {}: {}", line, e))?;
+            if op.is_extended() && dialect != Dialect::Extended {
+                return Err(format_err!(
+                    "line {}: {} is only available in the extended dialect",
+                    line,
+                    op
+                ));
+            }
+
+            let offset = match (op, operand) {
+                (Op::Hlt, None) => 0,
+                (_, None) => return Err(format_err!("line {}: {} needs an operand", line, op)),
+                (_, Some(Operand::Value(value))) => value,
+                (Op::Jmp | Op::Nop | Op::Jz, Some(Operand::Label(label))) => {
+                    let target = labels
+                        .get(label)
+                        .ok_or_else(|| format_err!("line {}: undefined label {}", line, label))?;
+                    target - addr
+                }
+                (_, Some(Operand::Label(label))) => {
+                    return Err(format_err!(
+                        "line {}: {} can't take a label ({}) as operand",
+                        line,
+                        op,
+                        label
+                    ))
+                }
+            };
+
+            Ok(Inst { op, offset })
+        })
+        .collect()
+}
+
+/// Canonical text of a program: one instruction per line, with explicit signed offsets.
+pub fn disassemble(prg: &[Inst]) -> String {
+    prg.iter().map(|inst| format!("{}\n", inst)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse_program, Console, ExitReason, EXAMPLE};
+    use super::*;
+
+    #[test]
+    fn test_line() {
+        assert_eq!(Ok(("", Line::default())), line("   "));
+        assert_eq!(
+            Ok((
+                "",
+                Line {
+                    label: Some("loop_1"),
+                    inst: Some(("jmp", Some(Operand::Label("end"))))
+                }
+            )),
+            line("loop_1 :  jmp end ")
+        );
+        assert_eq!(
+            Ok((
+                "",
+                Line {
+                    label: None,
+                    inst: Some(("acc", Some(Operand::Value(3))))
+                }
+            )),
+            line("acc 3")
+        );
+        assert!(line("acc +3 +4").is_err());
+    }
+
+    #[test]
+    fn test_assemble_standard() {
+        let source = "\
+# the example from the puzzle, with labels
+start:  nop +0
+        acc +1
+        jmp skip
+back:   acc +3
+        jmp -3      # numeric offsets still work
+        acc -99
+skip:   acc +1
+        jmp back
+
+        acc +6
+";
+        let prg = assemble(source, Dialect::Standard).unwrap();
+        let expected = parse_program(EXAMPLE).unwrap();
+        assert_eq!(expected, prg);
+        assert_eq!(format!("{}\n", EXAMPLE), disassemble(&prg));
+        assert_eq!(prg, parse_program(&disassemble(&prg)).unwrap());
+    }
+
+    #[test]
+    fn test_assemble_extended() {
+        let source = "\
+        acc +3
+loop:   jz end
+        acc -1
+        jmp loop
+end:    hlt
+";
+        assert!(assemble(source, Dialect::Standard)
+            .unwrap_err()
+            .to_string()
+            .contains("line 2: jz is only available in the extended dialect"));
+
+        let prg = assemble(source, Dialect::Extended).unwrap();
+        assert_eq!("acc +3\njz +3\nacc -1\njmp -2\nhlt +0\n", disassemble(&prg));
+
+        let mut console = Console::new(prg);
+        assert_eq!(ExitReason::InfiniteLoop { acc: 2, pc: 1 }, console.run());
+
+        let prg = assemble("acc +2\nmul -3\nhlt\nacc +100", Dialect::Extended).unwrap();
+        let mut console = Console::new(prg);
+        assert_eq!(ExitReason::Terminated, console.run());
+        assert_eq!(-6, console.state().acc);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let error = |source: &str| assemble(source, Dialect::Extended).unwrap_err().to_string();
+
+        assert_eq!(
+            "line 2: undefined label nowhere",
+            error("nop +0\njmp nowhere")
+        );
+        assert_eq!("line 2: duplicate label a", error("a: nop +0\na: nop +0"));
+        assert_eq!("line 1: unknown operation: foo", error("foo +1"));
+        assert_eq!("line 1: acc needs an operand", error("acc"));
+        assert_eq!(
+            "line 1: acc can't take a label (a) as operand",
+            error("a: acc a")
+        );
+        assert!(error("acc +1 # fine\n+3").starts_with("line 2: syntax error"));
+    }
+}
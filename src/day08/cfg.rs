@@ -0,0 +1,252 @@
+//! Static control flow analysis of console programs: which instructions can follow which,
+//! without running anything.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::ops::Range;
+
+use petgraph::{algo::tarjan_scc, dot::Dot, graphmap::DiGraphMap, visit::Dfs, Direction};
+
+use super::{jump_target, Inst, Op};
+
+/// A node of the control flow graph.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum Node {
+    /// The instruction at this address.
+    Inst(usize),
+    /// The address right after the last instruction: reaching it terminates the program.
+    End,
+    /// A jump target that is neither an instruction nor the end of the program.
+    Outside(i32),
+}
+
+/// How control gets from one node to the next.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Edge {
+    /// On to the following instruction.
+    Next,
+    /// A `jmp`, or a `jz` taking its branch.
+    Jump,
+    /// A `hlt`, going straight to the end.
+    Halt,
+}
+
+impl fmt::Display for Edge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Edge::Next => "",
+            Edge::Jump => "jump",
+            Edge::Halt => "halt",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// The control flow graph of a program. A `jz` has two outgoing edges, since which one is taken
+/// depends on the accumulator; every other instruction has exactly one.
+pub struct Cfg<'a> {
+    prg: &'a [Inst],
+    graph: DiGraphMap<Node, Edge>,
+}
+
+impl<'a> Cfg<'a> {
+    pub fn new(prg: &'a [Inst]) -> Cfg<'a> {
+        let mut cfg = Cfg {
+            prg,
+            graph: DiGraphMap::new(),
+        };
+        cfg.graph.add_node(Node::End);
+        for (addr, inst) in prg.iter().enumerate() {
+            let from = Node::Inst(addr);
+            let next = cfg.node(addr as i32 + 1);
+            let target = cfg.node(jump_target(addr as i32, inst.offset));
+            match inst.op {
+                Op::Acc | Op::Mul | Op::Nop => cfg.graph.add_edge(from, next, Edge::Next),
+                Op::Jmp => cfg.graph.add_edge(from, target, Edge::Jump),
+                Op::Jz => {
+                    cfg.graph.add_edge(from, next, Edge::Next);
+                    cfg.graph.add_edge(from, target, Edge::Jump)
+                }
+                Op::Hlt => cfg.graph.add_edge(from, Node::End, Edge::Halt),
+            };
+        }
+
+        cfg
+    }
+
+    /// The node for an address, whether or not it is inside the program.
+    pub fn node(&self, addr: i32) -> Node {
+        match addr {
+            addr if addr == self.prg.len() as i32 => Node::End,
+            addr if addr >= 0 && addr < self.prg.len() as i32 => Node::Inst(addr as usize),
+            addr => Node::Outside(addr),
+        }
+    }
+
+    /// Maximal runs of instructions that are always executed together: each one starts at the
+    /// first instruction, at a jump target or right after a jump or `hlt`, and ends before the
+    /// next such address.
+    pub fn basic_blocks(&self) -> Vec<Range<usize>> {
+        let len = self.prg.len();
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for (addr, inst) in self.prg.iter().enumerate() {
+            if let Op::Jmp | Op::Jz | Op::Hlt = inst.op {
+                leaders.insert(addr + 1);
+            }
+            if let Op::Jmp | Op::Jz = inst.op {
+                if let Node::Inst(target) = self.node(jump_target(addr as i32, inst.offset)) {
+                    leaders.insert(target);
+                }
+            }
+        }
+        leaders.insert(len);
+
+        leaders
+            .iter()
+            .zip(leaders.iter().skip(1))
+            .filter(|(start, _)| **start < len)
+            .map(|(&start, &end)| start..end)
+            .collect()
+    }
+
+    /// Nodes that some execution starting at address 0 may reach.
+    fn reachable(&self) -> HashSet<Node> {
+        let mut reachable = HashSet::new();
+        let mut dfs = Dfs::new(&self.graph, self.node(0));
+        while let Some(node) = dfs.next(&self.graph) {
+            reachable.insert(node);
+        }
+
+        reachable
+    }
+
+    /// Nodes from which the program may stop, either by terminating or by jumping outside.
+    fn may_stop(&self) -> HashSet<Node> {
+        let mut may_stop = HashSet::new();
+        let mut stack = self
+            .graph
+            .nodes()
+            .filter(|node| !matches!(node, Node::Inst(_)))
+            .collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            if may_stop.insert(node) {
+                stack.extend(self.graph.neighbors_directed(node, Direction::Incoming));
+            }
+        }
+
+        may_stop
+    }
+
+    /// Addresses of the instructions that can never be executed.
+    pub fn unreachable(&self) -> Vec<usize> {
+        let reachable = self.reachable();
+        (0..self.prg.len())
+            .filter(|addr| !reachable.contains(&Node::Inst(*addr)))
+            .collect()
+    }
+
+    /// Reachable loops that, once entered, can never be left whatever the accumulator holds,
+    /// each as a sorted list of addresses.
+    pub fn infinite_loops(&self) -> Vec<Vec<usize>> {
+        let reachable = self.reachable();
+        let may_stop = self.may_stop();
+        let mut loops = tarjan_scc(&self.graph)
+            .into_iter()
+            .filter(|scc| scc.len() > 1 || self.graph.contains_edge(scc[0], scc[0]))
+            .filter(|scc| reachable.contains(&scc[0]) && !may_stop.contains(&scc[0]))
+            .map(|scc| {
+                let mut addrs = scc
+                    .into_iter()
+                    .filter_map(|node| match node {
+                        Node::Inst(addr) => Some(addr),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                addrs.sort_unstable();
+                addrs
+            })
+            .collect::<Vec<_>>();
+        loops.sort_unstable();
+
+        loops
+    }
+
+    /// Whether the program is bound to loop forever, whatever branches a `jz` takes.
+    pub fn always_loops(&self) -> bool {
+        !self.may_stop().contains(&self.node(0))
+    }
+
+    /// Jumps to somewhere other than an instruction or the end of the program, as the address
+    /// of the jump and its target.
+    pub fn leaving_jumps(&self) -> Vec<(usize, i32)> {
+        let mut jumps = self
+            .graph
+            .all_edges()
+            .filter_map(|(from, to, _)| match (from, to) {
+                (Node::Inst(addr), Node::Outside(target)) => Some((addr, target)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        jumps.sort_unstable();
+
+        jumps
+    }
+
+    fn label(&self, node: Node) -> String {
+        match node {
+            Node::Inst(addr) => format!("{}: {}", addr, self.prg[addr]),
+            Node::End => "end".to_string(),
+            Node::Outside(addr) => format!("outside ({})", addr),
+        }
+    }
+
+    /// The graph in Graphviz DOT format, each instruction labelled with its address and text.
+    pub fn to_dot(&self) -> String {
+        let graph = self
+            .graph
+            .clone()
+            .into_graph::<u32>()
+            .map(|_, node| self.label(*node), |_, edge| *edge);
+        format!("{}", Dot::new(&graph))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day08::{parse_program, EXAMPLE};
+
+    #[test]
+    fn test_example() {
+        let prg = parse_program(EXAMPLE).unwrap();
+        let cfg = Cfg::new(&prg);
+
+        assert_eq!(vec![0..1, 1..3, 3..5, 5..6, 6..8, 8..9], cfg.basic_blocks());
+        assert_eq!(vec![5, 8], cfg.unreachable());
+        assert_eq!(vec![vec![1, 2, 3, 4, 6, 7]], cfg.infinite_loops());
+        assert!(cfg.always_loops());
+        assert!(cfg.leaving_jumps().is_empty());
+
+        let dot = cfg.to_dot();
+        assert!(dot.contains("label = \"2: jmp +4\""));
+        assert!(dot.contains("label = \"end\""));
+    }
+
+    #[test]
+    fn test_exits() {
+        let prg = parse_program("acc +1\nnop +5\njmp +2\njmp -7\nacc +1").unwrap();
+        let cfg = Cfg::new(&prg);
+
+        assert_eq!(vec![3], cfg.unreachable());
+        assert!(cfg.infinite_loops().is_empty());
+        assert!(!cfg.always_loops());
+        assert_eq!(vec![(3, -4)], cfg.leaving_jumps());
+
+        // a target beyond the range of addresses still leaves the program
+        let prg = parse_program("nop +0\njmp +2147483647").unwrap();
+        let cfg = Cfg::new(&prg);
+        assert_eq!(vec![(1, i32::MAX)], cfg.leaving_jumps());
+        assert_eq!(vec![0..2], cfg.basic_blocks());
+    }
+}
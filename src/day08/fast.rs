@@ -0,0 +1,236 @@
+//! A faster backend for running console programs many times over: instructions are decoded once
+//! into a dense array with absolute jump targets, visited addresses live in a bitset, and runs of
+//! consecutive `acc` instructions are executed in one go.
+
+use bitvec::prelude::*;
+
+use super::{jump_target, ExitReason, Inst, Op, State};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Code {
+    /// An `acc` followed by `run - 1` more of them, which add up to `sum` altogether. The
+    /// accumulator wraps around like on the console, so folding a run doesn't change the result.
+    Acc {
+        offset: i32,
+        sum: i32,
+        run: usize,
+    },
+    Mul(i32),
+    Jmp(i32),
+    Jz(i32),
+    Nop,
+    Hlt,
+}
+
+/// A program decoded for [`Compiled::run`], which gives the same results as running it on a
+/// [`Console`](super::Console).
+#[derive(Debug, Clone)]
+pub struct Compiled {
+    code: Vec<Code>,
+}
+
+impl Compiled {
+    pub fn new(prg: &[Inst]) -> Compiled {
+        let mut code = Vec::with_capacity(prg.len());
+        // decode backwards, so that each `acc` can extend the run starting right after it
+        for (addr, inst) in prg.iter().enumerate().rev() {
+            // a target that overflows is clamped out of bounds, as on the console
+            let target = jump_target(addr as i32, inst.offset);
+            code.push(match inst.op {
+                Op::Acc => match code.last() {
+                    Some(Code::Acc { sum, run, .. }) => Code::Acc {
+                        offset: inst.offset,
+                        sum: sum.wrapping_add(inst.offset),
+                        run: run + 1,
+                    },
+                    _ => Code::Acc {
+                        offset: inst.offset,
+                        sum: inst.offset,
+                        run: 1,
+                    },
+                },
+                Op::Mul => Code::Mul(inst.offset),
+                Op::Jmp => Code::Jmp(target),
+                Op::Jz => Code::Jz(target),
+                Op::Nop => Code::Nop,
+                Op::Hlt => Code::Hlt,
+            });
+        }
+        code.reverse();
+
+        Compiled { code }
+    }
+
+    /// Run the program from the start until it stops, returning why along with the final state,
+    /// exactly as [`Console::run`](super::Console::run) and
+    /// [`Console::state`](super::Console::state) would.
+    pub fn run(&self) -> (ExitReason, State) {
+        let len = self.code.len() as i32;
+        let mut visited = bitvec![0; self.code.len()];
+        let mut acc = 0;
+        let mut pc = 0;
+        loop {
+            if pc == len {
+                return (ExitReason::Terminated, State { acc, pc });
+            }
+            if pc < 0 || pc > len {
+                return (ExitReason::OutOfBounds { pc }, State { acc, pc });
+            }
+            let addr = pc as usize;
+            if visited[addr] {
+                return (ExitReason::InfiniteLoop { acc, pc }, State { acc, pc });
+            }
+
+            match self.code[addr] {
+                Code::Acc { offset, sum, run } => {
+                    let block = &mut visited[addr..addr + run];
+                    if block.not_any() {
+                        block.set_all(true);
+                        acc = acc.wrapping_add(sum);
+                        pc += run as i32;
+                    } else {
+                        // part of the run was entered before, and must stop the program as soon
+                        // as it's reached again
+                        visited.set(addr, true);
+                        acc = acc.wrapping_add(offset);
+                        pc += 1;
+                    }
+                }
+                Code::Mul(factor) => {
+                    visited.set(addr, true);
+                    acc = acc.wrapping_mul(factor);
+                    pc += 1;
+                }
+                Code::Jmp(target) => {
+                    visited.set(addr, true);
+                    pc = target;
+                }
+                Code::Jz(target) => {
+                    visited.set(addr, true);
+                    pc = if acc == 0 { target } else { pc + 1 };
+                }
+                Code::Nop => {
+                    visited.set(addr, true);
+                    pc += 1;
+                }
+                Code::Hlt => {
+                    visited.set(addr, true);
+                    pc = len;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day08::{flip, parse_program, Console, EXAMPLE};
+    use crate::rng::Rng;
+
+    fn reference(prg: &[Inst]) -> (ExitReason, State) {
+        let mut console = Console::new(prg.to_vec());
+        let reason = console.run();
+        (reason, console.state())
+    }
+
+    /// A random number in `0..n`.
+    fn below(rng: &mut Rng, n: u64) -> i32 {
+        rng.in_range(0..n) as i32
+    }
+
+    /// A random program, with plenty of `acc` runs and jumps landing in the middle of them.
+    fn random_program(rng: &mut Rng, extended: bool) -> Vec<Inst> {
+        let len = 1 + below(rng, 40);
+        (0..len)
+            .map(|_| {
+                let op = match below(rng, if extended { 10 } else { 7 }) {
+                    0..=3 => Op::Acc,
+                    4 | 5 => Op::Jmp,
+                    6 => Op::Nop,
+                    7 => Op::Mul,
+                    8 => Op::Jz,
+                    _ => Op::Hlt,
+                };
+                let offset = match op {
+                    // large enough to overflow now and then
+                    Op::Acc if below(rng, 4) == 0 => rng.next_u64() as i32,
+                    Op::Acc => below(rng, 21) - 10,
+                    Op::Mul => below(rng, 2001) - 1000,
+                    // as far as an offset goes, so that some targets overflow
+                    Op::Jmp | Op::Jz if below(rng, 20) == 0 => {
+                        if below(rng, 2) == 0 {
+                            i32::MAX
+                        } else {
+                            i32::MIN
+                        }
+                    }
+                    _ => below(rng, 2 * len as u64 + 5) - len - 2,
+                };
+                Inst { op, offset }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_example() {
+        let prg = parse_program(EXAMPLE).unwrap();
+        assert_eq!(reference(&prg), Compiled::new(&prg).run());
+        assert_eq!(
+            ExitReason::InfiniteLoop { acc: 5, pc: 1 },
+            Compiled::new(&prg).run().0
+        );
+    }
+
+    #[test]
+    fn test_entering_run_midway() {
+        // the run at 1..4 is entered at 2 first, then at 1
+        let prg = parse_program("jmp +2\nacc +1\nacc +2\nacc +3\nnop +0\njmp -4").unwrap();
+        let expected = (
+            ExitReason::InfiniteLoop { acc: 6, pc: 2 },
+            State { acc: 6, pc: 2 },
+        );
+        assert_eq!(expected, reference(&prg));
+        assert_eq!(expected, Compiled::new(&prg).run());
+    }
+
+    #[test]
+    fn test_overflow() {
+        // the folded run overflows, but never gets executed
+        let prg = parse_program("jmp +3\nacc +2000000000\nacc +2000000000").unwrap();
+        assert_eq!(reference(&prg), Compiled::new(&prg).run());
+
+        let prg = parse_program("acc +2000000000\nacc +2000000000\nacc +1").unwrap();
+        assert_eq!(reference(&prg), Compiled::new(&prg).run());
+
+        let prg = parse_program("nop +0\njmp +2147483647").unwrap();
+        assert_eq!(reference(&prg), Compiled::new(&prg).run());
+    }
+
+    #[test]
+    fn test_input_and_flips() {
+        let prg = parse_program(&std::fs::read_to_string("input/day08.txt").unwrap()).unwrap();
+        assert_eq!(reference(&prg), Compiled::new(&prg).run());
+        for addr in 0..prg.len() {
+            let mut patched = prg.clone();
+            patched[addr] = flip(&prg[addr]);
+            assert_eq!(reference(&patched), Compiled::new(&patched).run());
+        }
+    }
+
+    #[test]
+    fn test_random_programs() {
+        let mut rng = Rng::new(0x2020_1208);
+        for extended in &[false, true] {
+            for _ in 0..5000 {
+                let prg = random_program(&mut rng, *extended);
+                assert_eq!(
+                    reference(&prg),
+                    Compiled::new(&prg).run(),
+                    "{:?}",
+                    prg.iter().map(Inst::to_string).collect::<Vec<_>>()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Repairs that need more than one patched instruction: a bounded search for the smallest sets
+//! of patches that make a program terminate.
+
+use std::fmt;
+
+use super::{execute, flip, terminating_addrs, Inst, Op, State};
+
+/// What the search may change, and how many instructions at most.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SearchOptions {
+    pub max_patches: usize,
+    /// Besides flipping `jmp` and `nop`, allow pointing a `jmp` or `jz` at any other address of
+    /// the program or at its end.
+    pub offsets: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            max_patches: 2,
+            offsets: false,
+        }
+    }
+}
+
+/// One replaced instruction.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Patch {
+    pub addr: usize,
+    pub original: Inst,
+    pub patched: Inst,
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} to {} at address {}",
+            self.original, self.patched, self.addr
+        )
+    }
+}
+
+/// A set of patches that together make a program terminate, ordered by address.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MultiRepair {
+    pub patches: Vec<Patch>,
+    /// Value of the accumulator once the repaired program terminates.
+    pub acc: i32,
+}
+
+impl MultiRepair {
+    /// The program with every patch applied.
+    pub fn apply(&self, prg: &[Inst]) -> Vec<Inst> {
+        let mut patched = prg.to_vec();
+        for patch in &self.patches {
+            patched[patch.addr] = patch.patched;
+        }
+
+        patched
+    }
+}
+
+/// Every smallest set of at most `options.max_patches` patches that makes the program terminate.
+/// A program that already terminates gives a single repair without patches; none is returned if
+/// more patches would be needed.
+///
+/// Only instructions that actually get executed are worth patching, and a terminating run
+/// executes each instruction at most once, so the search follows the execution and decides
+/// whether to patch each instruction when it first reaches it. Once no patches are left, a run
+/// can only terminate from an address that terminates in the original program, which cuts off
+/// most branches straight away.
+pub fn repair_all(prg: &[Inst], options: SearchOptions) -> Vec<MultiRepair> {
+    let mut search = Search {
+        prg,
+        offsets: options.offsets,
+        terminating: terminating_addrs(prg),
+        patches: Vec::new(),
+        found: Vec::new(),
+    };
+    for max_patches in 0..=options.max_patches {
+        search.explore(State::default(), vec![false; prg.len()], max_patches);
+        if !search.found.is_empty() {
+            break;
+        }
+    }
+
+    search.found.sort_unstable_by_key(|repair| {
+        repair
+            .patches
+            .iter()
+            .map(|patch| (patch.addr, patch.patched.offset))
+            .collect::<Vec<_>>()
+    });
+    search.found
+}
+
+struct Search<'a> {
+    prg: &'a [Inst],
+    offsets: bool,
+    /// Addresses that terminate in the unpatched program.
+    terminating: Vec<bool>,
+    /// Patches applied on the way to the current state.
+    patches: Vec<Patch>,
+    found: Vec<MultiRepair>,
+}
+
+impl<'a> Search<'a> {
+    fn explore(&mut self, mut state: State, mut visited: Vec<bool>, budget: usize) {
+        let len = self.prg.len() as i32;
+        loop {
+            if state.pc == len {
+                let mut patches = self.patches.clone();
+                patches.sort_unstable_by_key(|patch| patch.addr);
+                self.found.push(MultiRepair {
+                    patches,
+                    acc: state.acc,
+                });
+                return;
+            }
+            if !(0..len).contains(&state.pc) || visited[state.pc as usize] {
+                return;
+            }
+            let addr = state.pc as usize;
+            if budget == 0 && !self.terminating[addr] {
+                return;
+            }
+
+            visited[addr] = true;
+            let inst = self.prg[addr];
+            if budget > 0 {
+                for patched in self.alternatives(addr, &inst) {
+                    self.patches.push(Patch {
+                        addr,
+                        original: inst,
+                        patched,
+                    });
+                    self.explore(execute(&patched, state, len), visited.clone(), budget - 1);
+                    self.patches.pop();
+                }
+            }
+            state = execute(&inst, state, len);
+        }
+    }
+
+    /// The instructions that may replace `inst` at `addr`.
+    fn alternatives(&self, addr: usize, inst: &Inst) -> Vec<Inst> {
+        let mut alternatives = Vec::new();
+        if flip(inst) != *inst {
+            alternatives.push(flip(inst));
+        }
+        if self.offsets && matches!(inst.op, Op::Jmp | Op::Jz) {
+            alternatives.extend(
+                (0..=self.prg.len())
+                    .map(|target| target as i32 - addr as i32)
+                    .filter(|offset| *offset != inst.offset)
+                    .map(|offset| Inst { offset, ..*inst }),
+            );
+        }
+
+        alternatives
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day08::{parse_program, Console, ExitReason, EXAMPLE};
+
+    fn program(input: &str) -> Vec<Inst> {
+        parse_program(input).unwrap()
+    }
+
+    #[test]
+    fn test_single_flip() {
+        let prg = program(EXAMPLE);
+        let repairs = repair_all(&prg, SearchOptions::default());
+
+        assert_eq!(1, repairs.len());
+        assert_eq!(8, repairs[0].acc);
+        assert_eq!(
+            vec![Patch {
+                addr: 7,
+                original: prg[7],
+                patched: flip(&prg[7]),
+            }],
+            repairs[0].patches
+        );
+    }
+
+    #[test]
+    fn test_already_terminating() {
+        let prg = program("acc +2\nnop -1");
+        assert_eq!(
+            vec![MultiRepair {
+                patches: vec![],
+                acc: 2
+            }],
+            repair_all(&prg, SearchOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_two_flips() {
+        let prg = program("jmp +0\nacc +1\njmp +0\nacc +2");
+        let options = SearchOptions::default();
+        let repairs = repair_all(&prg, options);
+        assert_eq!(1, repairs.len());
+        assert_eq!(
+            vec![0, 2],
+            repairs[0]
+                .patches
+                .iter()
+                .map(|patch| patch.addr)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(3, repairs[0].acc);
+
+        assert!(repair_all(
+            &prg,
+            SearchOptions {
+                max_patches: 1,
+                ..options
+            }
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_offsets() {
+        let prg = program("jmp +0\nacc +1\njmp +0\nacc +2");
+        let repairs = repair_all(
+            &prg,
+            SearchOptions {
+                max_patches: 2,
+                offsets: true,
+            },
+        );
+
+        let summary = repairs
+            .iter()
+            .map(|repair| (repair.patches[0].patched.to_string(), repair.acc))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![("jmp +3".to_string(), 2), ("jmp +4".to_string(), 0)],
+            summary
+        );
+    }
+
+    /// Compare against trying every pair of flips and running each patched program.
+    #[test]
+    fn test_matches_brute_force() {
+        let prg = program(
+            "jmp +2\nacc +1\nnop +3\njmp -2\nacc +5\njmp +2\njmp -6\nacc -1\njmp -1\nacc +7",
+        );
+        let flippable = (0..prg.len())
+            .filter(|addr| flip(&prg[*addr]) != prg[*addr])
+            .collect::<Vec<_>>();
+
+        let terminates = |addrs: &[usize]| {
+            let mut patched = prg.clone();
+            addrs
+                .iter()
+                .for_each(|addr| patched[*addr] = flip(&prg[*addr]));
+            let mut console = Console::new(patched);
+            if console.run() == ExitReason::Terminated {
+                Some(console.state().acc)
+            } else {
+                None
+            }
+        };
+        let mut expected = Vec::new();
+        for (i, a) in flippable.iter().enumerate() {
+            for b in &flippable[i + 1..] {
+                if terminates(&[*a]).is_none() && terminates(&[*b]).is_none() {
+                    if let Some(acc) = terminates(&[*a, *b]) {
+                        expected.push((vec![*a, *b], acc));
+                    }
+                }
+            }
+        }
+
+        let found = repair_all(&prg, SearchOptions::default())
+            .into_iter()
+            .map(|repair| {
+                let addrs = repair.patches.iter().map(|patch| patch.addr).collect();
+                (addrs, repair.acc)
+            })
+            .collect::<Vec<_>>();
+
+        assert!(!expected.is_empty());
+        assert_eq!(expected, found);
+    }
+}
@@ -0,0 +1,252 @@
+//! Execution traces of the console: what was executed, in which order, and what it did to the
+//! accumulator.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::{format_err, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{Console, ExitReason, Inst};
+
+/// One executed instruction.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub step: usize,
+    pub pc: i32,
+    pub inst: Inst,
+    pub acc_before: i32,
+    pub acc_after: i32,
+    /// How many times the instruction at `pc` has been executed so far, this one included.
+    pub visits: usize,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{:<5} pc {:<5} {:<8} acc {} -> {} (visit {})",
+            self.step,
+            self.pc,
+            self.inst.to_string(),
+            self.acc_before,
+            self.acc_after,
+            self.visits
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+    visits: HashMap<i32, usize>,
+}
+
+impl Trace {
+    pub(super) fn record(&mut self, pc: i32, inst: Inst, acc_before: i32, acc_after: i32) {
+        let visits = self.visits.entry(pc).or_insert(0);
+        *visits += 1;
+        self.entries.push(TraceEntry {
+            step: self.entries.len(),
+            pc,
+            inst,
+            acc_before,
+            acc_after,
+            visits: *visits,
+        });
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    /// One JSON object per line and per executed instruction.
+    pub fn to_json_lines(&self) -> Result<String> {
+        let mut output = String::new();
+        for entry in &self.entries {
+            output.push_str(&serde_json::to_string(entry)?);
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+
+    pub fn from_json_lines(input: &str) -> Result<Trace> {
+        let mut trace = Trace::default();
+        for (i, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: TraceEntry = serde_json::from_str(line)
+                .map_err(|e| format_err!("line {}: invalid trace entry: {}", i + 1, e))?;
+            *trace.visits.entry(entry.pc).or_insert(0) += 1;
+            trace.entries.push(entry);
+        }
+
+        Ok(trace)
+    }
+}
+
+/// Run the program with tracing enabled.
+pub fn record(prg: Vec<Inst>) -> (ExitReason, Trace) {
+    record_visits(prg, 1)
+}
+
+/// Run the program with tracing enabled, letting each instruction be executed up to
+/// `max_visits` times, so that loops show up in the trace more than once.
+pub fn record_visits(prg: Vec<Inst>, max_visits: usize) -> (ExitReason, Trace) {
+    let mut console = Console::new(prg);
+    console.set_max_visits(max_visits);
+    console.enable_trace();
+    let reason = console.run();
+    let trace = console.take_trace().unwrap_or_default();
+
+    (reason, trace)
+}
+
+/// Run the program again and check that it executes exactly what was recorded in `trace`, with
+/// as many visits per instruction as the trace has.
+pub fn replay(prg: Vec<Inst>, trace: &Trace) -> Result<ExitReason> {
+    let max_visits = trace.entries.iter().map(|e| e.visits).max().unwrap_or(1);
+    let (reason, replayed) = record_visits(prg, max_visits);
+    if let Some(diff) = diff(trace.entries(), replayed.entries())
+        .into_iter()
+        .find(|d| !matches!(d, StepDiff::Same(_)))
+    {
+        return Err(format_err!("replay diverged from trace:\n{}", diff));
+    }
+
+    Ok(reason)
+}
+
+/// How one step of a trace compares to the same step of another.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum StepDiff {
+    Same(TraceEntry),
+    Changed(TraceEntry, TraceEntry),
+    /// The step only exists in the old trace.
+    Removed(TraceEntry),
+    /// The step only exists in the new trace.
+    Added(TraceEntry),
+}
+
+impl fmt::Display for StepDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepDiff::Same(entry) => write!(f, "  {}", entry),
+            StepDiff::Changed(old, new) => write!(f, "- {}\n+ {}", old, new),
+            StepDiff::Removed(entry) => write!(f, "- {}", entry),
+            StepDiff::Added(entry) => write!(f, "+ {}", entry),
+        }
+    }
+}
+
+/// Compare two traces step by step.
+pub fn diff(old: &[TraceEntry], new: &[TraceEntry]) -> Vec<StepDiff> {
+    let mut diffs = old
+        .iter()
+        .zip(new)
+        .map(|(o, n)| {
+            if o == n {
+                StepDiff::Same(*o)
+            } else {
+                StepDiff::Changed(*o, *n)
+            }
+        })
+        .collect::<Vec<_>>();
+    diffs.extend(old.iter().skip(new.len()).copied().map(StepDiff::Removed));
+    diffs.extend(new.iter().skip(old.len()).copied().map(StepDiff::Added));
+
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{flip, parse_program, EXAMPLE};
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let (reason, trace) = record(parse_program(EXAMPLE).unwrap());
+
+        assert_eq!(ExitReason::InfiniteLoop { acc: 5, pc: 1 }, reason);
+        assert_eq!(
+            vec![0, 1, 2, 6, 7, 3, 4],
+            trace.entries().iter().map(|e| e.pc).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            TraceEntry {
+                step: 3,
+                pc: 6,
+                inst: Inst {
+                    op: super::super::Op::Acc,
+                    offset: 1
+                },
+                acc_before: 1,
+                acc_after: 2,
+                visits: 1
+            },
+            trace.entries()[3]
+        );
+    }
+
+    #[test]
+    fn test_record_visits() {
+        let prg = parse_program(EXAMPLE).unwrap();
+        let (reason, trace) = record_visits(prg.clone(), 3);
+
+        // the loop through 1, 2, 6, 7, 3, 4 goes round three times
+        assert_eq!(ExitReason::InfiniteLoop { acc: 15, pc: 1 }, reason);
+        assert_eq!(19, trace.entries().len());
+        assert_eq!(
+            vec![1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3],
+            trace.entries().iter().map(|e| e.visits).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            ExitReason::InfiniteLoop { acc: 15, pc: 1 },
+            replay(prg, &trace).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json_lines_round_trip() {
+        let (_, trace) = record(parse_program(EXAMPLE).unwrap());
+        let json = trace.to_json_lines().unwrap();
+
+        assert_eq!(7, json.lines().count());
+        assert_eq!(
+            r#"{"step":0,"pc":0,"inst":{"op":"nop","offset":0},"acc_before":0,"acc_after":0,"visits":1}"#,
+            json.lines().next().unwrap()
+        );
+        assert_eq!(trace, Trace::from_json_lines(&json).unwrap());
+        assert!(Trace::from_json_lines("{\"step\": 0}").is_err());
+    }
+
+    #[test]
+    fn test_replay_and_diff() {
+        let prg = parse_program(EXAMPLE).unwrap();
+        let (_, trace) = record(prg.clone());
+        assert_eq!(
+            ExitReason::InfiniteLoop { acc: 5, pc: 1 },
+            replay(prg.clone(), &trace).unwrap()
+        );
+
+        let mut repaired = prg.clone();
+        repaired[7] = flip(&repaired[7]);
+        assert!(replay(repaired.clone(), &trace).is_err());
+
+        let (_, repaired_trace) = record(repaired);
+        let diffs = diff(trace.entries(), repaired_trace.entries());
+        assert_eq!(
+            4,
+            diffs
+                .iter()
+                .filter(|d| matches!(d, StepDiff::Same(_)))
+                .count()
+        );
+        assert!(matches!(diffs[4], StepDiff::Changed(old, new) if old.pc == 7 && new.pc == 7));
+        assert!(matches!(diffs[5], StepDiff::Changed(old, new) if old.pc == 3 && new.pc == 8));
+        assert!(matches!(diffs[6], StepDiff::Removed(old) if old.pc == 4));
+        assert_eq!(7, diffs.len());
+    }
+}
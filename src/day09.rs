@@ -0,0 +1,314 @@
+//! Encoding Error: checking XMAS-encrypted data, where each number must be the sum of two of
+//! the numbers in the preamble right before it.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::BufRead;
+use std::ops::Range;
+
+use anyhow::{format_err, Context, Result};
+
+pub mod generate;
+
+/// The preamble length used by the puzzle.
+pub const PREAMBLE: usize = 25;
+
+/// Which two numbers of the preamble may be added together.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum Pairing {
+    /// Any two numbers at different positions, even when they are equal: with 5 appearing twice
+    /// in the preamble, 10 is valid.
+    #[default]
+    DistinctPositions,
+    /// Only two numbers with different values: 10 is never the sum of 5 and 5.
+    DistinctValues,
+}
+
+/// Checks numbers one at a time against the preamble formed by the numbers just before them.
+/// Only the preamble is kept in memory, along with a count of each value in it, so checking a
+/// number takes time proportional to the preamble length.
+#[derive(Debug, Clone)]
+pub struct XmasValidator {
+    preamble: usize,
+    pairing: Pairing,
+    window: VecDeque<u64>,
+    counts: HashMap<u64, usize>,
+    position: usize,
+}
+
+impl XmasValidator {
+    pub fn new(preamble: usize) -> XmasValidator {
+        XmasValidator::with_pairing(preamble, Pairing::default())
+    }
+
+    pub fn with_pairing(preamble: usize, pairing: Pairing) -> XmasValidator {
+        XmasValidator {
+            preamble,
+            pairing,
+            window: VecDeque::with_capacity(preamble + 1),
+            counts: HashMap::with_capacity(preamble + 1),
+            position: 0,
+        }
+    }
+
+    /// Position of the next number to be pushed.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Whether the preamble is complete, i.e. whether the next number will actually be checked.
+    pub fn is_primed(&self) -> bool {
+        self.window.len() == self.preamble
+    }
+
+    /// Whether `n` is the sum of two numbers of the current preamble.
+    pub fn is_sum_of_pair(&self, n: u64) -> bool {
+        self.window.iter().any(|&a| {
+            if a > n {
+                return false;
+            }
+            let b = n - a;
+            match self.counts.get(&b) {
+                None => false,
+                Some(_) if b != a => true,
+                Some(&count) => self.pairing == Pairing::DistinctPositions && count > 1,
+            }
+        })
+    }
+
+    /// Check the next number, then make it part of the preamble. Numbers in the initial preamble
+    /// are always valid; after that, every number is checked whether or not the previous ones
+    /// were valid.
+    pub fn push(&mut self, n: u64) -> bool {
+        let valid = !self.is_primed() || self.is_sum_of_pair(n);
+
+        self.window.push_back(n);
+        *self.counts.entry(n).or_insert(0) += 1;
+        if self.window.len() > self.preamble {
+            let old = self.window.pop_front().unwrap();
+            if let Some(count) = self.counts.get_mut(&old) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(&old);
+                }
+            }
+        }
+        self.position += 1;
+
+        valid
+    }
+
+    /// Position and value of every invalid number.
+    pub fn invalid_numbers<'a>(
+        &'a mut self,
+        nums: impl IntoIterator<Item = u64> + 'a,
+    ) -> impl Iterator<Item = (usize, u64)> + 'a {
+        nums.into_iter().filter_map(move |n| {
+            let position = self.position;
+            if self.push(n) {
+                None
+            } else {
+                Some((position, n))
+            }
+        })
+    }
+}
+
+/// Position and value of the first number that isn't the sum of two of the `preamble` numbers
+/// before it.
+pub fn first_invalid(nums: &[u64], preamble: usize) -> Option<(usize, u64)> {
+    XmasValidator::new(preamble)
+        .invalid_numbers(nums.iter().copied())
+        .next()
+}
+
+/// Check numbers as they are read, one per line, calling `on_invalid` with the position and
+/// value of each invalid one. Blank lines are skipped. Only the preamble is kept in memory,
+/// however long the input. Returns how many numbers were read.
+pub fn validate_stream<R, F>(
+    reader: R,
+    validator: &mut XmasValidator,
+    mut on_invalid: F,
+) -> Result<usize>
+where
+    R: BufRead,
+    F: FnMut(usize, u64),
+{
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let n = line
+            .parse::<u64>()
+            .with_context(|| format!("line {}: invalid number {:?}", line_no + 1, line))?;
+        let position = validator.position();
+        if !validator.push(n) {
+            on_invalid(position, n);
+        }
+    }
+
+    Ok(validator.position())
+}
+
+/// A run of at least two contiguous numbers adding up to a target.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ContiguousSum {
+    /// Positions of the numbers in the run.
+    pub range: Range<usize>,
+    pub min: u64,
+    pub max: u64,
+    /// The sum of the smallest and largest numbers in the run.
+    pub weakness: u64,
+}
+
+/// Find the first run of at least two contiguous numbers, by end position, that adds up to
+/// `target`. Since the numbers can't be negative, a window can slide over them in a single pass:
+/// extend it to the right, and shrink it from the left while its sum is too large. The sum is
+/// at most `target` plus one number, so it is kept in a `u128` where it can't overflow.
+pub fn find_contiguous_sum(nums: &[u64], target: u64) -> Result<ContiguousSum> {
+    let target = target as u128;
+    let mut start = 0;
+    let mut sum = 0u128;
+    for (end, &n) in nums.iter().enumerate() {
+        sum += n as u128;
+        while sum > target {
+            sum -= nums[start] as u128;
+            start += 1;
+        }
+        if sum == target && end > start {
+            let run = &nums[start..=end];
+            let min = *run.iter().min().unwrap();
+            let max = *run.iter().max().unwrap();
+            return Ok(ContiguousSum {
+                range: start..end + 1,
+                min,
+                max,
+                weakness: min + max,
+            });
+        }
+    }
+
+    Err(format_err!(
+        "No run of at least two contiguous numbers adds up to {}",
+        target
+    ))
+}
+
+pub fn parse_numbers(input: &str) -> Result<Vec<u64>> {
+    input
+        .lines()
+        .map(|s| s.trim().parse::<u64>().map_err(|e| e.into()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str =
+        "35\n20\n15\n25\n47\n40\n62\n55\n65\n95\n102\n117\n150\n182\n127\n219\n299\n277\n309\n576";
+
+    #[test]
+    fn test_example() {
+        let nums = parse_numbers(EXAMPLE).unwrap();
+        assert_eq!(Some((14, 127)), first_invalid(&nums, 5));
+
+        let invalid = XmasValidator::new(5)
+            .invalid_numbers(nums.iter().copied())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(14, 127)], invalid);
+    }
+
+    #[test]
+    fn test_stream() {
+        let input = format!("{}\n\n496\n1\n", EXAMPLE);
+        let mut invalid = Vec::new();
+        let count = validate_stream(
+            std::io::Cursor::new(input),
+            &mut XmasValidator::new(5),
+            |position, n| invalid.push((position, n)),
+        )
+        .unwrap();
+
+        assert_eq!(22, count);
+        assert_eq!(vec![(14, 127), (21, 1)], invalid);
+
+        let error = validate_stream(
+            std::io::Cursor::new("1\n2\nthree\n"),
+            &mut XmasValidator::new(5),
+            |_, _| {},
+        )
+        .unwrap_err();
+        assert!(error.to_string().starts_with("line 3:"));
+    }
+
+    #[test]
+    fn test_contiguous_sum() {
+        let nums = parse_numbers(EXAMPLE).unwrap();
+        assert_eq!(
+            ContiguousSum {
+                range: 2..6,
+                min: 15,
+                max: 47,
+                weakness: 62
+            },
+            find_contiguous_sum(&nums, 127).unwrap()
+        );
+        assert_eq!(1..3, find_contiguous_sum(&nums, 35).unwrap().range);
+
+        // a single number isn't a run, even when it's the target
+        assert!(find_contiguous_sum(&[5, 7], 5).is_err());
+        assert!(find_contiguous_sum(&[1, 2, 3], 7).is_err());
+        assert!(find_contiguous_sum(&[], 0).is_err());
+        assert_eq!(
+            4..6,
+            find_contiguous_sum(&[1, 9, 2, 5, 0, 3], 3).unwrap().range
+        );
+        assert_eq!(
+            3..5,
+            find_contiguous_sum(&[2, 9, 9, 5, 0], 5).unwrap().range
+        );
+
+        // sums beyond u64 don't overflow
+        assert!(find_contiguous_sum(&[u64::MAX - 1, 5], u64::MAX - 1).is_err());
+        assert_eq!(
+            1..3,
+            find_contiguous_sum(&[u64::MAX, u64::MAX - 1, 1], u64::MAX)
+                .unwrap()
+                .range
+        );
+    }
+
+    #[test]
+    fn test_puzzle_description() {
+        let mut validator = XmasValidator::new(PREAMBLE);
+        for n in 1..=25 {
+            assert!(validator.push(n));
+        }
+        assert!(validator.is_sum_of_pair(26));
+        assert!(validator.is_sum_of_pair(49));
+        assert!(!validator.is_sum_of_pair(100));
+        assert!(!validator.is_sum_of_pair(50));
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let nums = [5, 5, 3, 10, 8, 16];
+        let invalid = |pairing| {
+            XmasValidator::with_pairing(3, pairing)
+                .invalid_numbers(nums.iter().copied())
+                .collect::<Vec<_>>()
+        };
+
+        // 10 = 5 + 5; then 8 = 5 + 3 with [5, 3, 10]; 16 isn't a sum of [3, 10, 8]
+        assert_eq!(vec![(5, 16)], invalid(Pairing::DistinctPositions));
+        assert_eq!(vec![(3, 10), (5, 16)], invalid(Pairing::DistinctValues));
+
+        // a single 5 can't be used twice
+        let mut validator = XmasValidator::new(2);
+        validator.push(5);
+        validator.push(1);
+        assert!(!validator.push(10));
+    }
+}
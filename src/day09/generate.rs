@@ -0,0 +1,230 @@
+//! Seeded generation of XMAS data with known answers, for testing the validator on more than
+//! the puzzle input.
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use anyhow::{ensure, format_err, Result};
+
+use super::{find_contiguous_sum, XmasValidator};
+use crate::rng::Rng;
+
+/// What to generate.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Spec {
+    pub length: usize,
+    pub preamble: usize,
+    /// How many invalid numbers to inject, at least one since the first is the part 1 answer.
+    pub invalid: usize,
+    pub seed: u64,
+}
+
+/// Generated XMAS data, along with what the validator should find in it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Generated {
+    pub nums: Vec<u64>,
+    /// Position and value of each injected invalid number.
+    pub invalid: Vec<(usize, u64)>,
+    /// The contiguous run adding up to the first invalid number.
+    pub weakness_range: Range<usize>,
+}
+
+impl Generated {
+    /// The part 1 answer: the first invalid number.
+    pub fn part1(&self) -> u64 {
+        self.invalid[0].1
+    }
+
+    /// The part 2 answer: the sum of the smallest and largest numbers of the weakness range.
+    pub fn part2(&self) -> u64 {
+        let run = &self.nums[self.weakness_range.clone()];
+        run.iter().min().unwrap() + run.iter().max().unwrap()
+    }
+}
+
+/// Valid numbers are the sum of two of this many smallest numbers of their preamble, which keeps
+/// them from growing too fast.
+const SMALLEST: usize = 4;
+
+/// How many times to try a random run before giving up on finding one for the first invalid
+/// number.
+const RANGE_ATTEMPTS: usize = 10_000;
+
+/// Generate numbers where every one after the preamble is the sum of two numbers at different
+/// positions among the smallest of its preamble, except for `spec.invalid` numbers at random
+/// positions. The first of those is the sum of a random run of at least two earlier numbers, and
+/// no other run ending before it adds up to the same value. Later invalid numbers are random.
+///
+/// Valid numbers can only grow, so long sequences end up overflowing, and an error is returned
+/// when that happens. With a preamble of 25, that's after about 1400 numbers.
+pub fn generate(spec: Spec) -> Result<Generated> {
+    ensure!(
+        spec.preamble >= 2,
+        "the preamble needs at least two numbers"
+    );
+    ensure!(
+        spec.invalid >= 1 && spec.invalid <= spec.length.saturating_sub(spec.preamble),
+        "can't inject {} invalid numbers after a preamble of {} in {} numbers",
+        spec.invalid,
+        spec.preamble,
+        spec.length
+    );
+
+    let mut rng = Rng::new(spec.seed);
+    let mut positions = BTreeSet::new();
+    while positions.len() < spec.invalid {
+        positions.insert(rng.in_range(spec.preamble as u64..spec.length as u64) as usize);
+    }
+
+    let mut validator = XmasValidator::new(spec.preamble);
+    let mut nums = Vec::with_capacity(spec.length);
+    let mut invalid = Vec::with_capacity(spec.invalid);
+    let mut weakness_range = 0..0;
+    for pos in 0..spec.length {
+        let n = if pos < spec.preamble {
+            rng.in_range(1..100)
+        } else if invalid.is_empty() && positions.contains(&pos) {
+            let (range, n) = weakness(&mut rng, &nums, &validator)?;
+            weakness_range = range;
+            n
+        } else if positions.contains(&pos) {
+            random_invalid(&mut rng, &nums[pos - spec.preamble..], &validator)?
+        } else {
+            let mut smallest = nums[pos - spec.preamble..].to_vec();
+            smallest.sort_unstable();
+            smallest.truncate(SMALLEST);
+            let a = rng.in_range(0..smallest.len() as u64) as usize;
+            let b = (a + rng.in_range(1..smallest.len() as u64) as usize) % smallest.len();
+            smallest[a].checked_add(smallest[b]).ok_or_else(|| {
+                format_err!(
+                    "numbers overflow after {} of them, try a longer preamble",
+                    pos
+                )
+            })?
+        };
+
+        if positions.contains(&pos) {
+            invalid.push((pos, n));
+        }
+        let valid = validator.push(n);
+        debug_assert_eq!(valid, !positions.contains(&pos));
+        nums.push(n);
+    }
+
+    Ok(Generated {
+        nums,
+        invalid,
+        weakness_range,
+    })
+}
+
+/// A random run of earlier numbers, whose sum isn't the sum of a pair in the current preamble,
+/// and which is the first run of numbers to add up to it.
+fn weakness(rng: &mut Rng, nums: &[u64], validator: &XmasValidator) -> Result<(Range<usize>, u64)> {
+    for _ in 0..RANGE_ATTEMPTS {
+        let len = rng.in_range(2..nums.len().min(20) as u64 + 1) as usize;
+        let start = rng.in_range(0..(nums.len() - len) as u64 + 1) as usize;
+        let range = start..start + len;
+        let sum = match nums[range.clone()]
+            .iter()
+            .try_fold(0u64, |sum, &n| sum.checked_add(n))
+        {
+            Some(sum) => sum,
+            None => continue,
+        };
+        if validator.is_sum_of_pair(sum) {
+            continue;
+        }
+        if find_contiguous_sum(nums, sum).ok().map(|run| run.range) == Some(range.clone()) {
+            return Ok((range, sum));
+        }
+    }
+
+    Err(format_err!(
+        "couldn't find a run of numbers for the first invalid number"
+    ))
+}
+
+/// A random number that isn't the sum of a pair in the current preamble.
+fn random_invalid(rng: &mut Rng, window: &[u64], validator: &XmasValidator) -> Result<u64> {
+    let max = *window.iter().max().unwrap();
+    let mut n = rng.in_range(1..max.saturating_mul(2).max(2));
+    while validator.is_sum_of_pair(n) {
+        n = n
+            .checked_add(1)
+            .ok_or_else(|| format_err!("numbers overflow, try a longer preamble"))?;
+    }
+
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day09::{first_invalid, PREAMBLE};
+
+    #[test]
+    fn test_answers() {
+        for seed in 0..20 {
+            let spec = Spec {
+                length: 1000,
+                preamble: PREAMBLE,
+                invalid: 3,
+                seed,
+            };
+            let generated = generate(spec).unwrap();
+            assert_eq!(1000, generated.nums.len());
+
+            let found = XmasValidator::new(PREAMBLE)
+                .invalid_numbers(generated.nums.iter().copied())
+                .collect::<Vec<_>>();
+            assert_eq!(generated.invalid, found);
+            assert_eq!(
+                Some(generated.invalid[0]),
+                first_invalid(&generated.nums, PREAMBLE)
+            );
+
+            let run = find_contiguous_sum(&generated.nums, generated.part1()).unwrap();
+            assert_eq!(generated.weakness_range, run.range);
+            assert_eq!(generated.part2(), run.weakness);
+        }
+    }
+
+    #[test]
+    fn test_seeded() {
+        let spec = Spec {
+            length: 100,
+            preamble: 5,
+            invalid: 2,
+            seed: 42,
+        };
+        assert_eq!(generate(spec).unwrap(), generate(spec).unwrap());
+        assert_ne!(
+            generate(spec).unwrap().nums,
+            generate(Spec { seed: 43, ..spec }).unwrap().nums
+        );
+    }
+
+    #[test]
+    fn test_bad_specs() {
+        let spec = Spec {
+            length: 10,
+            preamble: 5,
+            invalid: 1,
+            seed: 0,
+        };
+        assert!(generate(Spec {
+            preamble: 1,
+            ..spec
+        })
+        .is_err());
+        assert!(generate(Spec { invalid: 0, ..spec }).is_err());
+        assert!(generate(Spec { invalid: 6, ..spec }).is_err());
+        assert!(generate(Spec {
+            length: 10_000,
+            preamble: 2,
+            ..spec
+        })
+        .is_err());
+    }
+}
@@ -1,8 +1,14 @@
 use std::env;
 use std::path::Path;
+use std::str::FromStr;
 
 use anyhow::*;
 
+pub mod day07;
+pub mod day08;
+pub mod day09;
+pub mod rng;
+
 /// Read the input file for the current day's puzzle, i.e. `input/dayxx.txt`, and return its content as a String.
 pub fn input_string() -> Result<String> {
     let executable_name = env::args_os()
@@ -16,3 +22,59 @@ pub fn input_string() -> Result<String> {
     infile.set_extension("txt");
     std::fs::read_to_string(&infile).context("Could not read input file")
 }
+
+/// The argument `n` places after `flag` on the command line, or `None` if `flag` isn't given.
+/// Fails if `flag` is given without that many arguments after it.
+pub fn arg_after<'a>(args: &'a [String], flag: &str, n: usize) -> Result<Option<&'a str>> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .map(|pos| {
+            args.get(pos + n)
+                .map(String::as_str)
+                .ok_or_else(|| format_err!("{}: missing argument", flag))
+        })
+        .transpose()
+}
+
+/// The value right after `flag` on the command line, or `default` if `flag` isn't given.
+pub fn flag_value<T>(args: &[String], flag: &str, default: T) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match arg_after(args, flag, 1)? {
+        Some(value) => value
+            .parse()
+            .with_context(|| format!("{}: invalid value {:?}", flag, value)),
+        None => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags() {
+        let args = [
+            "day08",
+            "--trace",
+            "out.json",
+            "--max-visits",
+            "3",
+            "--offsets",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>();
+
+        assert_eq!(Some("out.json"), arg_after(&args, "--trace", 1).unwrap());
+        assert_eq!(Some("3"), arg_after(&args, "--trace", 3).unwrap());
+        assert_eq!(None, arg_after(&args, "--replay", 1).unwrap());
+        assert!(arg_after(&args, "--offsets", 1).is_err());
+
+        assert_eq!(3, flag_value(&args, "--max-visits", 1).unwrap());
+        assert_eq!(1, flag_value(&args, "--seed", 1).unwrap());
+        assert!(flag_value(&args, "--trace", 1).is_err());
+    }
+}